@@ -0,0 +1,142 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::history::UsageSnapshot;
+use crate::usage_api::UsageLimit;
+
+pub const SESSION_LABEL: &str = "Current session";
+/// Sessions are 5-hour rolling windows that start at the first message.
+const SESSION_WINDOW: Duration = Duration::hours(5);
+/// The burn rate reflects the last hour, not the whole session.
+const LOOKBACK: Duration = Duration::hours(1);
+/// Shorter spans are too noisy to extrapolate from.
+const MIN_SPAN: Duration = Duration::minutes(10);
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct BurnRate {
+    /// Session usage right now (0.0 - 1.0).
+    pub usage_pct: f64,
+    /// Fraction of the session limit used per hour recently.
+    pub pct_per_hour: Option<f64>,
+    /// From ccusage's active block, when available.
+    pub tokens_per_hour: Option<f64>,
+    pub tokens_per_hour_display: Option<String>,
+    pub resets_at: Option<String>,
+    /// When the limit will be hit at this pace; `None` if the session resets first.
+    pub projected_exhaustion_at: Option<String>,
+    pub minutes_to_exhaustion: Option<i64>,
+    pub minutes_to_reset: Option<i64>,
+    /// Projected session usage at reset as a fraction of the limit; above 1.0
+    /// the limit will be hit before the reset.
+    pub pace_vs_limit: Option<f64>,
+}
+
+/// Burn rate of the session `session` observed at `now`, from the snapshots
+/// recorded in its current window.
+pub fn compute(snapshots: &[UsageSnapshot], session: &UsageLimit, now: DateTime<Utc>) -> BurnRate {
+    let mut rate = BurnRate {
+        usage_pct: session.usage_pct,
+        resets_at: session.reset_at.clone(),
+        ..Default::default()
+    };
+
+    let reset_at = session
+        .reset_at
+        .as_deref()
+        .and_then(|r| DateTime::parse_from_rfc3339(r).ok())
+        .map(|r| r.with_timezone(&Utc));
+    let window_start = reset_at.map(|r| r - SESSION_WINDOW);
+    let since = window_start.map_or(now - LOOKBACK, |w| w.max(now - LOOKBACK));
+
+    let earliest = snapshots
+        .iter()
+        .filter(|s| s.timestamp >= since.timestamp() && s.timestamp < now.timestamp())
+        .filter_map(|s| s.buckets.get(SESSION_LABEL).map(|p| (s.timestamp, *p)))
+        .min_by_key(|(t, _)| *t);
+    let (t0, p0) = match earliest {
+        Some(e) => e,
+        None => return rate,
+    };
+    let span_secs = now.timestamp() - t0;
+    if span_secs < MIN_SPAN.num_seconds() {
+        return rate;
+    }
+
+    let per_hour = ((session.usage_pct - p0) / (span_secs as f64 / 3600.0)).max(0.0);
+    rate.pct_per_hour = Some(per_hour);
+
+    let reset_at = match reset_at {
+        Some(r) if r > now => r,
+        _ => return rate,
+    };
+    let hours_left = (reset_at - now).num_seconds() as f64 / 3600.0;
+    rate.minutes_to_reset = Some((reset_at - now).num_minutes());
+    rate.pace_vs_limit = Some(session.usage_pct + per_hour * hours_left);
+
+    if per_hour > 0.0 && session.usage_pct < 1.0 {
+        let hours_to_full = (1.0 - session.usage_pct) / per_hour;
+        if hours_to_full < hours_left {
+            let at = now + Duration::seconds((hours_to_full * 3600.0) as i64);
+            rate.projected_exhaustion_at = Some(at.to_rfc3339());
+            rate.minutes_to_exhaustion = Some((at - now).num_minutes());
+        }
+    }
+    rate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2026-03-02T{}:00Z", time)).unwrap().with_timezone(&Utc)
+    }
+
+    fn snap(time: &str, pct: f64) -> UsageSnapshot {
+        UsageSnapshot {
+            timestamp: at(time).timestamp(),
+            buckets: HashMap::from([(SESSION_LABEL.to_string(), pct)]),
+            projects: HashMap::new(),
+            models: HashMap::new(),
+        }
+    }
+
+    fn session(pct: f64, resets: &str) -> UsageLimit {
+        UsageLimit { label: SESSION_LABEL.to_string(), usage_pct: pct, reset_at: Some(at(resets).to_rfc3339()) }
+    }
+
+    #[test]
+    fn pace_is_measured_over_the_last_hour() {
+        let snapshots = [snap("10:00", 0.0), snap("11:00", 0.3), snap("11:30", 0.4)];
+        let rate = compute(&snapshots, &session(0.5, "14:00"), at("12:00"));
+        assert!((rate.pct_per_hour.unwrap() - 0.2).abs() < 1e-9);
+        assert_eq!(rate.minutes_to_reset, Some(120));
+        assert!((rate.pace_vs_limit.unwrap() - 0.9).abs() < 1e-9);
+        // Resets before the limit is reached
+        assert_eq!(rate.minutes_to_exhaustion, None);
+    }
+
+    #[test]
+    fn exhaustion_is_projected_when_it_comes_before_the_reset() {
+        let rate = compute(&[snap("11:00", 0.25)], &session(0.75, "14:00"), at("12:00"));
+        assert_eq!(rate.minutes_to_exhaustion, Some(30));
+        assert_eq!(rate.projected_exhaustion_at.as_deref(), Some("2026-03-02T12:30:00+00:00"));
+    }
+
+    #[test]
+    fn snapshots_from_before_the_window_are_ignored() {
+        // The window started at 11:30; the 11:00 snapshot belongs to the last one
+        let snapshots = [snap("11:00", 0.9), snap("11:40", 0.1)];
+        let rate = compute(&snapshots, &session(0.2, "16:30"), at("12:00"));
+        assert!((rate.pct_per_hour.unwrap() - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn short_or_missing_history_gives_no_rate() {
+        let rate = compute(&[snap("11:55", 0.1)], &session(0.2, "14:00"), at("12:00"));
+        assert_eq!((rate.pct_per_hour, rate.minutes_to_reset), (None, None));
+        assert_eq!(rate.usage_pct, 0.2);
+        assert!(compute(&[], &session(0.2, "14:00"), at("12:00")).pct_per_hour.is_none());
+    }
+}
@@ -0,0 +1,395 @@
+use crate::storage;
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::process::Command;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CcusageError {
+    #[error("ccusage not found. Install with: npm install -g ccusage")]
+    NotFound,
+    #[error("ccusage failed: {0}")]
+    ExecutionError(String),
+    #[error("Failed to parse output: {0}")]
+    ParseError(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CostData {
+    pub today: f64,
+    pub week: f64,
+    pub month: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct CcusageOutput {
+    daily: Vec<DailyEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DailyEntry {
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default, alias = "totalCost")]
+    total_cost: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlocksOutput {
+    blocks: Vec<BlockEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockEntry {
+    #[serde(default)]
+    is_active: bool,
+    #[serde(default)]
+    burn_rate: Option<BlockBurnRate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockBurnRate {
+    tokens_per_minute: Option<f64>,
+}
+
+/// Costs and when they were computed, as kept in memory and on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedCosts {
+    /// Unix seconds.
+    pub fetched_at: i64,
+    pub data: CostData,
+}
+
+impl CachedCosts {
+    pub fn is_fresh(&self) -> bool {
+        chrono::Utc::now().timestamp() - self.fetched_at < CACHE_TTL_SECS
+    }
+}
+
+/// A ccusage run is slow, so its result is reused for this long.
+const CACHE_TTL_SECS: i64 = 300;
+
+/// Where the cache stood when a ccusage run started; see [`CostCache::start_fetch`].
+#[derive(Debug, Clone, Copy)]
+pub struct CostFetch {
+    started_at: i64,
+    invalidations: u64,
+}
+
+/// The last ccusage result. With a backing file it survives restarts, so a
+/// cold start can show the previous numbers while a fresh run is in flight.
+#[derive(Clone)]
+pub struct CostCache {
+    data: std::sync::Arc<Mutex<Option<CachedCosts>>>,
+    /// Bumped by every `invalidate`.
+    invalidations: std::sync::Arc<AtomicU64>,
+    /// The `invalidations` count the cached costs were computed after; they
+    /// are invalidated while it lags behind.
+    settled: std::sync::Arc<AtomicU64>,
+    path: Option<PathBuf>,
+}
+
+impl Default for CostCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CostCache {
+    pub fn new() -> Self {
+        Self {
+            data: std::sync::Arc::new(Mutex::new(None)),
+            invalidations: std::sync::Arc::new(AtomicU64::new(0)),
+            settled: std::sync::Arc::new(AtomicU64::new(0)),
+            path: None,
+        }
+    }
+
+    /// A cache persisted at `path`, starting from whatever was saved there.
+    pub fn with_file(path: PathBuf) -> Self {
+        let loaded = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<CachedCosts>(&contents).ok());
+        if let Some(ref c) = loaded {
+            crate::log(&format!("ccusage: loaded cached costs from {}", c.fetched_at));
+        }
+        Self {
+            data: std::sync::Arc::new(Mutex::new(loaded)),
+            invalidations: std::sync::Arc::new(AtomicU64::new(0)),
+            settled: std::sync::Arc::new(AtomicU64::new(0)),
+            path: Some(path),
+        }
+    }
+
+    /// Costs computed within the TTL and not invalidated since.
+    pub fn get(&self) -> Option<CostData> {
+        if self.settled.load(Ordering::SeqCst) != self.invalidations.load(Ordering::SeqCst) {
+            return None;
+        }
+        self.get_any().filter(|c| c.is_fresh()).map(|c| c.data)
+    }
+
+    /// Make the next fetch run ccusage again, for when new usage is known to
+    /// have happened. `get_any` still returns the old costs meanwhile.
+    pub fn invalidate(&self) {
+        self.invalidations.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Call before running ccusage, and pass the result to `set`.
+    pub fn start_fetch(&self) -> CostFetch {
+        CostFetch {
+            started_at: chrono::Utc::now().timestamp(),
+            invalidations: self.invalidations.load(Ordering::SeqCst),
+        }
+    }
+
+    /// The last known costs, however old.
+    pub fn get_any(&self) -> Option<CachedCosts> {
+        self.data.lock().ok()?.clone()
+    }
+
+    /// Cache the costs from the run `fetch` started. They are dropped if the
+    /// cache was invalidated during the run, which may have missed that usage.
+    pub fn set(&self, data: CostData, fetch: CostFetch) {
+        let Ok(mut lock) = self.data.lock() else { return };
+        if self.invalidations.load(Ordering::SeqCst) != fetch.invalidations {
+            crate::log("ccusage: costs invalidated during the run, not caching them");
+            return;
+        }
+        let cached = CachedCosts {
+            fetched_at: fetch.started_at,
+            data,
+        };
+        if let Some(ref path) = self.path {
+            match serde_json::to_string(&cached) {
+                Ok(json) => {
+                    let _ = storage::write_tracked("cost_cache", path, &json);
+                }
+                Err(e) => crate::log(&format!("ccusage: cache serialize error: {}", e)),
+            }
+        }
+        *lock = Some(cached);
+        self.settled.store(fetch.invalidations, Ordering::SeqCst);
+    }
+}
+
+pub async fn fetch_costs(cache: &CostCache) -> Result<CostData, CcusageError> {
+    if let Some(cached) = cache.get() {
+        return Ok(cached);
+    }
+    let fetch = cache.start_fetch();
+
+    let today = Local::now().date_naive();
+
+    // ccusage expects YYYYMMDD format
+    let since = today
+        .checked_sub_days(chrono::Days::new(30))
+        .unwrap_or(today)
+        .format("%Y%m%d")
+        .to_string();
+
+    let ccusage_path = find_ccusage()?;
+
+    let output = Command::new(&ccusage_path)
+        .args(["daily", "--since", &since, "--json"])
+        .env("PATH", build_path())
+        .output()
+        .await
+        .map_err(|e| CcusageError::ExecutionError(e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(CcusageError::ExecutionError(stderr.to_string()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // ccusage wraps output in { "daily": [...] }
+    let parsed: CcusageOutput = serde_json::from_str(&stdout)
+        .map_err(|e| CcusageError::ParseError(format!("{}: {}", e, &stdout[..stdout.len().min(200)])))?;
+
+    let today_str = today.format("%Y-%m-%d").to_string();
+    let week_ago = today
+        .checked_sub_days(chrono::Days::new(7))
+        .unwrap_or(today);
+
+    let mut costs = CostData::default();
+
+    for entry in &parsed.daily {
+        let cost = entry.total_cost.unwrap_or(0.0);
+        let date_str = entry.date.as_deref().unwrap_or("");
+
+        costs.month += cost;
+
+        if let Ok(date) = NaiveDate::parse_from_str(date_str, "%Y-%m-%d") {
+            if date >= week_ago {
+                costs.week += cost;
+            }
+        }
+
+        if date_str == today_str {
+            costs.today = cost;
+        }
+    }
+
+    // Round to 2 decimal places
+    costs.today = (costs.today * 100.0).round() / 100.0;
+    costs.week = (costs.week * 100.0).round() / 100.0;
+    costs.month = (costs.month * 100.0).round() / 100.0;
+
+    cache.set(costs.clone(), fetch);
+    Ok(costs)
+}
+
+/// Tokens per hour in the active 5-hour billing block, per `ccusage blocks`.
+/// `None` when there is no active block.
+pub async fn fetch_tokens_per_hour() -> Result<Option<f64>, CcusageError> {
+    let ccusage_path = find_ccusage()?;
+
+    let output = Command::new(&ccusage_path)
+        .args(["blocks", "--active", "--json"])
+        .env("PATH", build_path())
+        .output()
+        .await
+        .map_err(|e| CcusageError::ExecutionError(e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(CcusageError::ExecutionError(stderr.to_string()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let parsed: BlocksOutput = serde_json::from_str(&stdout)
+        .map_err(|e| CcusageError::ParseError(format!("{}: {}", e, &stdout[..stdout.len().min(200)])))?;
+
+    Ok(parsed
+        .blocks
+        .iter()
+        .find(|b| b.is_active)
+        .and_then(|b| b.burn_rate.as_ref())
+        .and_then(|r| r.tokens_per_minute)
+        .map(|tpm| (tpm * 60.0).round()))
+}
+
+fn home_dir_string() -> Option<String> {
+    dirs::home_dir().map(|p| p.to_string_lossy().to_string())
+}
+
+/// Use this ccusage executable instead of searching for one. Only read with
+/// the `test-hooks` feature.
+#[cfg(feature = "test-hooks")]
+pub const CCUSAGE_PATH_ENV_VAR: &str = "CLAUDIT_CCUSAGE_PATH";
+
+fn find_ccusage() -> Result<String, CcusageError> {
+    #[cfg(feature = "test-hooks")]
+    if let Ok(path) = std::env::var(CCUSAGE_PATH_ENV_VAR) {
+        if !path.is_empty() {
+            return Ok(path);
+        }
+    }
+
+    let home = home_dir_string().unwrap_or_default();
+
+    #[cfg(target_os = "macos")]
+    let candidates: Vec<String> = vec![
+        format!("{}/.npm-global/bin/ccusage", home),
+        "/opt/homebrew/bin/ccusage".to_string(),
+        "/usr/local/bin/ccusage".to_string(),
+    ];
+
+    #[cfg(target_os = "windows")]
+    let candidates: Vec<String> = {
+        let appdata = std::env::var("APPDATA").unwrap_or_default();
+        vec![
+            format!("{}\\npm\\ccusage.cmd", appdata),
+            format!("{}\\AppData\\Roaming\\npm\\ccusage.cmd", home),
+        ]
+    };
+
+    #[cfg(target_os = "linux")]
+    let candidates: Vec<String> = vec![
+        format!("{}/.npm-global/bin/ccusage", home),
+        "/usr/local/bin/ccusage".to_string(),
+        "/usr/bin/ccusage".to_string(),
+    ];
+
+    for path in &candidates {
+        if std::path::Path::new(path).exists() {
+            return Ok(path.to_string());
+        }
+    }
+
+    // Fall back to which/where
+    let which_cmd = if cfg!(target_os = "windows") { "where" } else { "which" };
+    if let Ok(output) = std::process::Command::new(which_cmd).arg("ccusage").output() {
+        if output.status.success() {
+            let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !path.is_empty() {
+                return Ok(path);
+            }
+        }
+    }
+
+    Err(CcusageError::NotFound)
+}
+
+fn build_path() -> String {
+    let home = home_dir_string().unwrap_or_default();
+
+    #[cfg(target_os = "macos")]
+    let extra: Vec<String> = vec![
+        format!("{}/.npm-global/bin", home),
+        "/usr/local/bin".to_string(),
+        "/opt/homebrew/bin".to_string(),
+    ];
+
+    #[cfg(target_os = "windows")]
+    let extra: Vec<String> = {
+        let appdata = std::env::var("APPDATA").unwrap_or_default();
+        vec![format!("{}\\npm", appdata)]
+    };
+
+    #[cfg(target_os = "linux")]
+    let extra: Vec<String> = vec![
+        format!("{}/.npm-global/bin", home),
+        "/usr/local/bin".to_string(),
+        "/usr/bin".to_string(),
+    ];
+
+    let sep = if cfg!(target_os = "windows") { ";" } else { ":" };
+    let current = std::env::var("PATH").unwrap_or_default();
+    format!("{}{}{}", extra.join(sep), sep, current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn costs(today: f64) -> CostData {
+        CostData { today, ..Default::default() }
+    }
+
+    #[test]
+    fn invalidation_during_a_run_discards_its_result() {
+        let cache = CostCache::new();
+        let fetch = cache.start_fetch();
+        cache.set(costs(1.0), fetch);
+        assert_eq!(cache.get().map(|c| c.today), Some(1.0));
+        assert_eq!(cache.get_any().unwrap().fetched_at, fetch.started_at);
+
+        // New usage lands while the next run is still reading transcripts
+        let fetch = cache.start_fetch();
+        cache.invalidate();
+        cache.set(costs(2.0), fetch);
+        assert!(cache.get().is_none());
+        assert_eq!(cache.get_any().map(|c| c.data.today), Some(1.0));
+
+        cache.set(costs(3.0), cache.start_fetch());
+        assert_eq!(cache.get().map(|c| c.today), Some(3.0));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::history::{project_usage, ProjectUsage, UsageSnapshot};
+use crate::log;
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Projects and models kept per event.
+const TOP_N: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub model: String,
+    pub tokens: u64,
+}
+
+/// A limit that reached 100%, and what used it up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExhaustionEvent {
+    pub label: String,
+    /// First snapshot at 100%, in Unix seconds.
+    pub started_at: i64,
+    /// First snapshot below 100% again; `None` while still locked out.
+    pub ended_at: Option<i64>,
+    pub lockout_minutes: Option<i64>,
+    /// Biggest contributors from the window's start (its last reset) up to the lockout.
+    pub top_projects: Vec<ProjectUsage>,
+    pub top_models: Vec<ModelUsage>,
+}
+
+impl ExhaustionEvent {
+    /// Whether both events describe the same lockout. Compaction can move a
+    /// start by a few minutes, so overlapping spans of one limit count as one.
+    fn same_as(&self, other: &ExhaustionEvent) -> bool {
+        self.label == other.label
+            && self.started_at <= other.ended_at.unwrap_or(i64::MAX)
+            && other.started_at <= self.ended_at.unwrap_or(i64::MAX)
+    }
+}
+
+/// Every time a limit hit 100% in `snapshots` (oldest first).
+pub fn detect(snapshots: &[UsageSnapshot]) -> Vec<ExhaustionEvent> {
+    let labels: BTreeSet<&str> = snapshots.iter().flat_map(|s| s.buckets.keys().map(String::as_str)).collect();
+    let mut events = Vec::new();
+    for label in labels {
+        let series: Vec<(usize, f64)> = snapshots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| s.buckets.get(label).map(|&v| (i, v)))
+            .collect();
+        let mut k = 0;
+        while k < series.len() {
+            if series[k].1 < 1.0 {
+                k += 1;
+                continue;
+            }
+            let start = k;
+            while k < series.len() && series[k].1 >= 1.0 {
+                k += 1;
+            }
+            let started_at = snapshots[series[start].0].timestamp;
+            let ended_at = series.get(k).map(|&(i, _)| snapshots[i].timestamp);
+
+            // Walk back to the window's last reset: the run of non-decreasing values
+            let mut from = start;
+            while from > 0 && series[from - 1].1 <= series[from].1 {
+                from -= 1;
+            }
+            let window = &snapshots[series[from].0..=series[start].0];
+
+            let mut top_projects = project_usage(window, label, i64::MIN);
+            top_projects.truncate(TOP_N);
+            events.push(ExhaustionEvent {
+                label: label.to_string(),
+                started_at,
+                ended_at,
+                lockout_minutes: ended_at.map(|e| (e - started_at) / 60),
+                top_projects,
+                top_models: top_models(&window[1..]),
+            });
+        }
+    }
+    events.sort_by_key(|e| e.started_at);
+    events
+}
+
+fn top_models(snapshots: &[UsageSnapshot]) -> Vec<ModelUsage> {
+    let mut totals: HashMap<&str, u64> = HashMap::new();
+    for (model, &tokens) in snapshots.iter().flat_map(|s| &s.models) {
+        *totals.entry(model).or_default() += tokens;
+    }
+    let mut models: Vec<ModelUsage> = totals
+        .into_iter()
+        .map(|(model, tokens)| ModelUsage { model: model.to_string(), tokens })
+        .collect();
+    models.sort_by(|a, b| b.tokens.cmp(&a.tokens).then(a.model.cmp(&b.model)));
+    models.truncate(TOP_N);
+    models
+}
+
+/// Archived events at `path`, oldest first. They outlive the history they
+/// were detected in.
+pub fn load_events(path: &Path) -> Vec<ExhaustionEvent> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log(&format!("exhaustion: parse error: {}", e));
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Add the events found in `snapshots` to the archive at `path`, updating
+/// ones already there (e.g. a lockout that has since ended). Returns the
+/// number of new events.
+pub fn update_archive(path: &Path, snapshots: &[UsageSnapshot]) -> std::io::Result<usize> {
+    let detected = detect(snapshots);
+    if detected.is_empty() {
+        return Ok(0);
+    }
+    let mut events = load_events(path);
+    let before = events.clone();
+    let mut added = 0;
+    for event in detected {
+        match events.iter_mut().find(|e| e.same_as(&event)) {
+            // Finished events are final; later history may have lost their start
+            Some(existing) if existing.ended_at.is_some() => {}
+            Some(existing) => {
+                // Keep the earliest start; the snapshot it came from may have been compacted
+                existing.ended_at = event.ended_at;
+                existing.lockout_minutes = event.ended_at.map(|e| (e - existing.started_at) / 60);
+                existing.top_projects = event.top_projects;
+                existing.top_models = event.top_models;
+            }
+            None => {
+                log(&format!("exhaustion: {} hit 100% at {}", event.label, event.started_at));
+                events.push(event);
+                added += 1;
+            }
+        }
+    }
+    if events == before {
+        return Ok(0);
+    }
+    events.sort_by_key(|e| e.started_at);
+    let json = serde_json::to_string(&events)?;
+    storage::write_tracked("exhaustion_events", path, &json)?;
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(timestamp: i64, pct: f64, project: &str, model: &str, tokens: u64) -> UsageSnapshot {
+        UsageSnapshot {
+            timestamp,
+            buckets: HashMap::from([("Current session".to_string(), pct)]),
+            projects: HashMap::from([(project.to_string(), tokens)]),
+            models: HashMap::from([(model.to_string(), tokens)]),
+        }
+    }
+
+    #[test]
+    fn detects_lockout_and_its_contributors() {
+        let snapshots = vec![
+            snap(0, 0.9, "/old", "claude-opus-4", 999),
+            // Reset, then the window that ran out
+            snap(600, 0.2, "/web", "claude-sonnet-4", 100),
+            snap(1200, 0.7, "/api", "claude-opus-4", 500),
+            snap(1800, 1.0, "/api", "claude-opus-4", 300),
+            snap(2400, 1.0, "/web", "claude-sonnet-4", 0),
+            snap(4200, 0.0, "/web", "claude-sonnet-4", 0),
+        ];
+        let events = detect(&snapshots);
+        assert_eq!(events.len(), 1);
+        let e = &events[0];
+        assert_eq!((e.started_at, e.ended_at, e.lockout_minutes), (1800, Some(4200), Some(40)));
+        assert_eq!(e.top_projects[0].project, "/api");
+        assert_eq!(e.top_models[0].model, "claude-opus-4");
+        assert_eq!(e.top_models[0].tokens, 800);
+        assert!(e.top_projects.iter().all(|p| p.project != "/old"));
+    }
+}
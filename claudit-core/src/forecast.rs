@@ -0,0 +1,198 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use serde::Serialize;
+
+use crate::history::UsageSnapshot;
+use crate::usage_api::UsageData;
+
+/// Weekly limits to forecast, with the short name used in summaries.
+const WEEKLY_LIMITS: &[(&str, &str)] = &[
+    ("Current week (all models)", "weekly"),
+    ("Current week (Opus only)", "Opus"),
+    ("Current week (Sonnet only)", "Sonnet"),
+];
+const WEEK: Duration = Duration::days(7);
+/// Fewer points or a shorter span than this is not a trend.
+const MIN_POINTS: usize = 3;
+const MIN_SPAN: Duration = Duration::hours(2);
+/// How far from "exactly one week ago" a snapshot may be to count as last week's value.
+const PREVIOUS_WEEK_TOLERANCE: Duration = Duration::hours(2);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LimitForecast {
+    pub label: String,
+    /// Utilization now (0.0 - 1.0).
+    pub usage_pct: f64,
+    pub resets_at: String,
+    /// Fitted utilization gained per day this week.
+    pub pct_per_day: Option<f64>,
+    pub projected_at_reset: Option<f64>,
+    /// When the limit runs out at the fitted pace, if before the reset.
+    pub exhausts_at: Option<String>,
+    /// Utilization at the same point in the previous week.
+    pub previous_week_pct: Option<f64>,
+    /// `usage_pct - previous_week_pct`.
+    pub vs_previous_week: Option<f64>,
+    /// e.g. "On track to hit the Opus limit Thursday".
+    pub summary: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct Forecast {
+    pub limits: Vec<LimitForecast>,
+}
+
+/// Least-squares slope of `points` as (seconds, value), in value per second.
+fn slope(points: &[(i64, f64)]) -> f64 {
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|(t, _)| *t as f64).sum::<f64>() / n;
+    let mean_v = points.iter().map(|(_, v)| *v).sum::<f64>() / n;
+    let (mut num, mut den) = (0.0, 0.0);
+    for (t, v) in points {
+        let dt = *t as f64 - mean_t;
+        num += dt * (v - mean_v);
+        den += dt * dt;
+    }
+    if den == 0.0 {
+        0.0
+    } else {
+        num / den
+    }
+}
+
+/// Forecast every weekly limit in `usage` from the snapshots of its current window.
+pub fn compute(snapshots: &[UsageSnapshot], usage: &UsageData, now: DateTime<Utc>) -> Forecast {
+    let mut forecast = Forecast::default();
+
+    for &(label, short) in WEEKLY_LIMITS {
+        let limit = match usage.limits.iter().find(|l| l.label == label) {
+            Some(l) => l,
+            None => continue,
+        };
+        let reset_at = match limit
+            .reset_at
+            .as_deref()
+            .and_then(|r| DateTime::parse_from_rfc3339(r).ok())
+        {
+            Some(r) => r.with_timezone(&Utc),
+            None => continue,
+        };
+        let window_start = reset_at - WEEK;
+
+        let series = |from: DateTime<Utc>, to: DateTime<Utc>| -> Vec<(i64, f64)> {
+            snapshots
+                .iter()
+                .filter(|s| s.timestamp >= from.timestamp() && s.timestamp < to.timestamp())
+                .filter_map(|s| s.buckets.get(label).map(|v| (s.timestamp, *v)))
+                .collect()
+        };
+
+        let mut points = series(window_start, now);
+        points.push((now.timestamp(), limit.usage_pct));
+
+        let mut lf = LimitForecast {
+            label: label.to_string(),
+            usage_pct: limit.usage_pct,
+            resets_at: reset_at.to_rfc3339(),
+            pct_per_day: None,
+            projected_at_reset: None,
+            exhausts_at: None,
+            previous_week_pct: None,
+            vs_previous_week: None,
+            summary: None,
+        };
+
+        let span = points.last().map_or(0, |p| p.0) - points.first().map_or(0, |p| p.0);
+        if points.len() >= MIN_POINTS && span >= MIN_SPAN.num_seconds() && reset_at > now {
+            let per_sec = slope(&points).max(0.0);
+            let secs_left = (reset_at - now).num_seconds() as f64;
+            let projected = limit.usage_pct + per_sec * secs_left;
+            lf.pct_per_day = Some(per_sec * 86400.0);
+            lf.projected_at_reset = Some(projected);
+
+            if limit.usage_pct < 1.0 && projected >= 1.0 {
+                let at = now + Duration::seconds(((1.0 - limit.usage_pct) / per_sec) as i64);
+                lf.exhausts_at = Some(at.to_rfc3339());
+                lf.summary = Some(format!(
+                    "On track to hit the {} limit {}",
+                    short,
+                    at.with_timezone(&Local).format("%A")
+                ));
+            }
+        }
+
+        let week_ago = now - WEEK;
+        lf.previous_week_pct = series(week_ago - PREVIOUS_WEEK_TOLERANCE, week_ago + PREVIOUS_WEEK_TOLERANCE)
+            .into_iter()
+            .min_by_key(|(t, _)| (t - week_ago.timestamp()).abs())
+            .map(|(_, v)| v);
+        lf.vs_previous_week = lf.previous_week_pct.map(|prev| limit.usage_pct - prev);
+
+        forecast.limits.push(lf);
+    }
+    forecast
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage_api::UsageLimit;
+    use std::collections::HashMap;
+
+    const WEEKLY: &str = "Current week (all models)";
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2026-{}:00Z", time)).unwrap().with_timezone(&Utc)
+    }
+
+    fn snap(time: &str, pct: f64) -> UsageSnapshot {
+        UsageSnapshot {
+            timestamp: at(time).timestamp(),
+            buckets: HashMap::from([(WEEKLY.to_string(), pct)]),
+            projects: HashMap::new(),
+            models: HashMap::new(),
+        }
+    }
+
+    /// Weekly usage at `pct`, resetting Sunday noon.
+    fn usage(pct: f64) -> UsageData {
+        UsageData {
+            limits: vec![
+                UsageLimit { label: WEEKLY.to_string(), usage_pct: pct, reset_at: Some(at("03-08T12:00").to_rfc3339()) },
+                UsageLimit { label: "Current session".to_string(), usage_pct: 0.9, reset_at: None },
+            ],
+            extra_usage: None,
+            plan: None,
+        }
+    }
+
+    #[test]
+    fn steady_pace_is_projected_to_the_reset() {
+        let snapshots = [snap("03-02T12:00", 0.1), snap("03-03T12:00", 0.2)];
+        let forecast = compute(&snapshots, &usage(0.3), at("03-04T12:00"));
+        assert_eq!(forecast.limits.len(), 1);
+        let lf = &forecast.limits[0];
+        assert!((lf.pct_per_day.unwrap() - 0.1).abs() < 1e-9);
+        assert!((lf.projected_at_reset.unwrap() - 0.7).abs() < 1e-9);
+        assert_eq!((lf.exhausts_at.as_ref(), lf.summary.as_ref()), (None, None));
+    }
+
+    #[test]
+    fn fast_pace_is_projected_to_run_out() {
+        let snapshots = [snap("03-02T12:00", 0.2), snap("03-03T12:00", 0.4)];
+        let lf = &compute(&snapshots, &usage(0.6), at("03-04T12:00")).limits[0];
+        let exhausts_at = DateTime::parse_from_rfc3339(lf.exhausts_at.as_deref().unwrap()).unwrap();
+        assert!((exhausts_at.timestamp() - at("03-06T12:00").timestamp()).abs() <= 1);
+        assert!(lf.summary.as_deref().unwrap().starts_with("On track to hit the weekly limit "));
+    }
+
+    #[test]
+    fn last_weeks_value_is_the_snapshot_nearest_a_week_ago() {
+        // Before this window, so only used for the comparison
+        let snapshots = [snap("02-25T10:30", 0.9), snap("02-25T12:30", 0.5), snap("03-04T11:00", 0.3)];
+        let lf = &compute(&snapshots, &usage(0.3), at("03-04T12:00")).limits[0];
+        assert_eq!(lf.previous_week_pct, Some(0.5));
+        assert!((lf.vs_previous_week.unwrap() + 0.2).abs() < 1e-9);
+        // Two points an hour apart are not a trend
+        assert_eq!(lf.pct_per_day, None);
+    }
+}
@@ -0,0 +1,480 @@
+use crate::log;
+use crate::projects::Activity;
+use crate::storage;
+use crate::usage_api::UsageData;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// How long snapshots are kept and how densely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    /// Older snapshots are dropped.
+    pub max_age_secs: i64,
+    /// A snapshot closer than this to the previous one replaces it.
+    pub min_interval_secs: i64,
+    /// Snapshots older than this are thinned to one per `compacted_interval_secs`.
+    pub compact_after_secs: i64,
+    pub compacted_interval_secs: i64,
+}
+
+impl Default for Retention {
+    /// Two weeks (so the forecast can compare against the previous week), at
+    /// most one snapshot a minute, thinned to one per 15 minutes after two days.
+    fn default() -> Self {
+        Self {
+            max_age_secs: 14 * 24 * 3600,
+            min_interval_secs: 60,
+            compact_after_secs: 2 * 24 * 3600,
+            compacted_interval_secs: 15 * 60,
+        }
+    }
+}
+
+/// Policy applied whenever history is written.
+static RETENTION: Mutex<Option<Retention>> = Mutex::new(None);
+
+pub fn set_retention(retention: Retention) {
+    *RETENTION.lock().unwrap() = Some(retention);
+}
+
+fn retention() -> Retention {
+    RETENTION.lock().unwrap().unwrap_or_default()
+}
+
+/// Keep at most about a week of 5-minute polls in memory while the disk is unwritable.
+const MAX_PENDING: usize = 2016;
+
+static HISTORY_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+/// Snapshots that couldn't be written yet, per history file, oldest first.
+static PENDING: Mutex<Vec<(PathBuf, UsageSnapshot)>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageSnapshot {
+    pub timestamp: i64,
+    pub buckets: HashMap<String, f64>,
+    /// Tokens used per project (working directory) since the previous snapshot.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub projects: HashMap<String, u64>,
+    /// Tokens used per model since the previous snapshot.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub models: HashMap<String, u64>,
+}
+
+/// A project's part of the growth of one limit over a period.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectUsage {
+    pub project: String,
+    pub tokens: u64,
+    /// Share of the limit attributed to the project (0.0 - 1.0 of the whole limit).
+    pub usage_pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageHistory {
+    pub snapshots: Vec<UsageSnapshot>,
+}
+
+const LABEL_MIGRATIONS: &[(&str, &str)] = &[
+    ("Session (5hr rolling)", "Current session"),
+    ("Weekly All Models", "Current week (all models)"),
+    ("Weekly Sonnet", "Current week (Sonnet only)"),
+    ("Weekly Opus", "Current week (Opus only)"),
+];
+
+fn migrate_labels(history: &mut UsageHistory) {
+    for snapshot in &mut history.snapshots {
+        for &(old, new) in LABEL_MIGRATIONS {
+            if let Some(val) = snapshot.buckets.remove(old) {
+                snapshot.buckets.insert(new.to_string(), val);
+            }
+        }
+    }
+}
+
+/// History at `path`, including snapshots still waiting to be written there.
+pub fn load_history_at(path: &Path) -> UsageHistory {
+    let mut history = read_file(path);
+    let pending = PENDING.lock().unwrap();
+    history
+        .snapshots
+        .extend(pending.iter().filter(|(p, _)| p == path).map(|(_, s)| s.clone()));
+    history
+}
+
+fn read_file(path: &Path) -> UsageHistory {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            let mut history: UsageHistory = serde_json::from_str(&contents).unwrap_or_else(|e| {
+                log(&format!("history: parse error: {}", e));
+                UsageHistory { snapshots: vec![] }
+            });
+            migrate_labels(&mut history);
+            history
+        }
+        Err(_) => UsageHistory { snapshots: vec![] },
+    }
+}
+
+/// Record `usage` now, with `activity` as the tokens used since the previous
+/// snapshot (see [`crate::projects::ActivityTracker`]).
+pub fn save_snapshot_at(path: &Path, usage: &UsageData, activity: Activity) {
+    let mut buckets = HashMap::new();
+    for limit in &usage.limits {
+        buckets.insert(limit.label.clone(), limit.usage_pct);
+    }
+    let snapshot = UsageSnapshot {
+        timestamp: chrono::Utc::now().timestamp(),
+        buckets,
+        projects: activity.projects,
+        models: activity.models,
+    };
+
+    {
+        let mut pending = PENDING.lock().unwrap();
+        pending.push((path.to_path_buf(), snapshot));
+        if pending.len() > MAX_PENDING {
+            log("history: pending queue full, dropping oldest snapshot");
+            pending.remove(0);
+        }
+    }
+    flush_at(path);
+}
+
+/// Append the pending snapshots for `path` to the file. They stay queued if
+/// the write fails. Returns whether the write succeeded.
+fn flush_at(path: &Path) -> bool {
+    let lock = HISTORY_LOCK.get_or_init(|| Mutex::new(()));
+    let _guard = lock.lock().unwrap();
+
+    let queued: Vec<UsageSnapshot> = PENDING
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(p, _)| p == path)
+        .map(|(_, s)| s.clone())
+        .collect();
+    if queued.is_empty() {
+        return true;
+    }
+
+    let mut history = read_file(path);
+    history.snapshots.extend(queued.iter().cloned());
+    compact(&mut history.snapshots, &retention(), chrono::Utc::now().timestamp());
+
+    let json = match serde_json::to_string(&history) {
+        Ok(json) => json,
+        Err(e) => {
+            log(&format!("history: serialize error: {}", e));
+            return false;
+        }
+    };
+    match storage::write_tracked("history", path, &json) {
+        Ok(()) => {
+            let written: Vec<i64> = queued.iter().map(|s| s.timestamp).collect();
+            PENDING
+                .lock()
+                .unwrap()
+                .retain(|(p, s)| p != path || !written.contains(&s.timestamp));
+            true
+        }
+        Err(e) => {
+            log(&format!("history: write error, {} snapshot(s) queued: {}", queued.len(), e));
+            false
+        }
+    }
+}
+
+/// Merge `newer` into `older`: each limit keeps its highest value, so a reset
+/// inside the group doesn't hide the peak before it, and token counts add up.
+fn merge_into(older: &mut UsageSnapshot, newer: UsageSnapshot) {
+    older.timestamp = newer.timestamp;
+    for (label, pct) in newer.buckets {
+        let value = older.buckets.entry(label).or_insert(pct);
+        *value = value.max(pct);
+    }
+    for (project, tokens) in newer.projects {
+        *older.projects.entry(project).or_default() += tokens;
+    }
+    for (model, tokens) in newer.models {
+        *older.models.entry(model).or_default() += tokens;
+    }
+}
+
+/// Apply `retention` to `snapshots` as of `now`: drop expired ones, thin old
+/// ones to one per compacted interval, and merge recent ones that are within
+/// the minimum interval of the first in their group. A merged group takes
+/// the time of its last snapshot (see [`merge_into`] for the values).
+pub fn compact(snapshots: &mut Vec<UsageSnapshot>, retention: &Retention, now: i64) {
+    snapshots.sort_by_key(|s| s.timestamp);
+    let cutoff = now - retention.max_age_secs;
+    let old_before = now - retention.compact_after_secs;
+
+    let mut kept: Vec<UsageSnapshot> = Vec::with_capacity(snapshots.len());
+    // Timestamp of the first snapshot merged into `kept.last()`; measuring
+    // from the merged (latest) time would chain steady polls into one
+    let mut group_start = 0i64;
+    for snap in snapshots.drain(..).filter(|s| s.timestamp >= cutoff) {
+        if let Some(last) = kept.last_mut() {
+            let same_slot = if snap.timestamp < old_before {
+                let slot = retention.compacted_interval_secs.max(1);
+                group_start.div_euclid(slot) == snap.timestamp.div_euclid(slot)
+            } else {
+                snap.timestamp - group_start < retention.min_interval_secs
+            };
+            if same_slot {
+                merge_into(last, snap);
+                continue;
+            }
+        }
+        group_start = snap.timestamp;
+        kept.push(snap);
+    }
+    *snapshots = kept;
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CompactStats {
+    pub before: usize,
+    pub after: usize,
+}
+
+/// Rewrite the history at `path` under the current retention policy.
+pub fn compact_history_at(path: &Path) -> std::io::Result<CompactStats> {
+    let lock = HISTORY_LOCK.get_or_init(|| Mutex::new(()));
+    let _guard = lock.lock().unwrap();
+
+    let mut history = read_file(path);
+    let before = history.snapshots.len();
+    compact(&mut history.snapshots, &retention(), chrono::Utc::now().timestamp());
+    let after = history.snapshots.len();
+
+    let json = serde_json::to_string(&history).map_err(std::io::Error::other)?;
+    storage::write_tracked("history", path, &json)?;
+    log(&format!("history: compacted {} -> {} snapshots", before, after));
+    Ok(CompactStats { before, after })
+}
+
+pub fn pending_count() -> usize {
+    PENDING.lock().unwrap().len()
+}
+
+/// Retry writing every queued snapshot. True if nothing is left pending.
+pub fn retry_pending() -> bool {
+    let mut paths: Vec<PathBuf> = PENDING.lock().unwrap().iter().map(|(p, _)| p.clone()).collect();
+    paths.dedup();
+    let failed = paths.iter().filter(|p| !flush_at(p)).count();
+    failed == 0
+}
+
+/// How much of limit `label` each project used since `since` (Unix seconds).
+/// Each rise of the limit between two snapshots is split across the projects
+/// in proportion to their tokens over that interval; after a reset the whole
+/// new value counts as the rise.
+pub fn project_usage(snapshots: &[UsageSnapshot], label: &str, since: i64) -> Vec<ProjectUsage> {
+    let mut totals: HashMap<&str, (u64, f64)> = HashMap::new();
+    for pair in snapshots.windows(2) {
+        let (prev, cur) = (&pair[0], &pair[1]);
+        if cur.timestamp < since || cur.projects.is_empty() {
+            continue;
+        }
+        let (Some(&before), Some(&after)) = (prev.buckets.get(label), cur.buckets.get(label)) else {
+            continue;
+        };
+        let rise = if after >= before { after - before } else { after };
+        let tokens: u64 = cur.projects.values().sum();
+        for (project, &t) in &cur.projects {
+            let entry = totals.entry(project).or_default();
+            entry.0 += t;
+            if tokens > 0 {
+                entry.1 += rise * t as f64 / tokens as f64;
+            }
+        }
+    }
+
+    let mut usage: Vec<ProjectUsage> = totals
+        .into_iter()
+        .map(|(project, (tokens, usage_pct))| ProjectUsage { project: project.to_string(), tokens, usage_pct })
+        .collect();
+    usage.sort_by(|a, b| b.usage_pct.total_cmp(&a.usage_pct).then(b.tokens.cmp(&a.tokens)));
+    usage
+}
+
+/// Width of the intervals a usage series is aggregated into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    FiveMinutes,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    pub fn secs(self) -> i64 {
+        match self {
+            Resolution::FiveMinutes => 5 * 60,
+            Resolution::Hour => 3600,
+            Resolution::Day => 24 * 3600,
+        }
+    }
+}
+
+/// One interval of a usage series; values are 0.0 - 1.0 of the limit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesPoint {
+    /// Start of the interval, Unix seconds.
+    pub timestamp: i64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Snapshots in the interval.
+    pub samples: usize,
+}
+
+/// Limit `label` between `from` and `to` (Unix seconds, inclusive), one point
+/// per `resolution` interval that has snapshots. Intervals start on the hour
+/// or day in local time, where `utc_offset` gives the seconds local time is
+/// east of UTC at a Unix time, so days stay aligned across DST changes.
+pub fn usage_series(
+    snapshots: &[UsageSnapshot],
+    label: &str,
+    from: i64,
+    to: i64,
+    resolution: Resolution,
+    utc_offset: impl Fn(i64) -> i64,
+) -> Vec<SeriesPoint> {
+    let width = resolution.secs();
+    let mut points: BTreeMap<i64, SeriesPoint> = BTreeMap::new();
+    for snapshot in snapshots {
+        if snapshot.timestamp < from || snapshot.timestamp > to {
+            continue;
+        }
+        let Some(&value) = snapshot.buckets.get(label) else {
+            continue;
+        };
+        let local_start = (snapshot.timestamp + utc_offset(snapshot.timestamp)).div_euclid(width) * width;
+        // The offset at the start of the interval, which differs from the
+        // snapshot's when the clocks changed in between
+        let start = local_start - utc_offset(local_start - utc_offset(snapshot.timestamp));
+        let point = points.entry(start).or_insert(SeriesPoint {
+            timestamp: start,
+            avg: 0.0,
+            min: value,
+            max: value,
+            samples: 0,
+        });
+        // Running sum, divided once every snapshot is in
+        point.avg += value;
+        point.min = point.min.min(value);
+        point.max = point.max.max(value);
+        point.samples += 1;
+    }
+    points
+        .into_values()
+        .map(|mut p| {
+            p.avg /= p.samples as f64;
+            p
+        })
+        .collect()
+}
+
+/// Timestamp of the newest snapshot at `path`, queued ones included.
+pub fn last_snapshot_at(path: &Path) -> Option<i64> {
+    load_history_at(path).snapshots.iter().map(|s| s.timestamp).max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snap(timestamp: i64, pct: f64, tokens: u64) -> UsageSnapshot {
+        UsageSnapshot {
+            timestamp,
+            buckets: HashMap::from([("Current session".to_string(), pct)]),
+            projects: HashMap::from([("/work/app".to_string(), tokens)]),
+            models: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn compact_thins_old_and_merges_dense_snapshots() {
+        let retention = Retention::default();
+        let now = 30 * 24 * 3600;
+        let old = now - 3 * 24 * 3600;
+        let mut snapshots = vec![
+            snap(now - 20 * 24 * 3600, 0.1, 1),
+            // Three polls inside one 15-minute slot, two days back
+            snap(old, 0.1, 10),
+            snap(old + 300, 0.2, 20),
+            snap(old + 600, 0.3, 30),
+            // Recent polls 30s apart
+            snap(now - 90, 0.5, 5),
+            snap(now - 60, 0.6, 5),
+            snap(now, 0.7, 5),
+        ];
+        compact(&mut snapshots, &retention, now);
+
+        let stamps: Vec<i64> = snapshots.iter().map(|s| s.timestamp).collect();
+        assert_eq!(stamps, vec![old + 600, now - 60, now]);
+        assert_eq!(snapshots[0].buckets["Current session"], 0.3);
+        assert_eq!(snapshots[0].projects["/work/app"], 60);
+        assert_eq!(snapshots[1].projects["/work/app"], 10);
+    }
+
+    #[test]
+    fn steady_polls_are_not_chained_into_one_snapshot() {
+        let now = 30 * 24 * 3600;
+        // Every 30s for five minutes, then a session reset
+        let mut snapshots: Vec<UsageSnapshot> = (0..10).map(|i| snap(now - 300 + i * 30, 0.5 + i as f64 * 0.01, 1)).collect();
+        snapshots.push(snap(now, 0.0, 1));
+        compact(&mut snapshots, &Retention::default(), now);
+
+        let stamps: Vec<i64> = snapshots.iter().map(|s| s.timestamp).collect();
+        assert_eq!(stamps, vec![now - 270, now - 210, now - 150, now - 90, now - 30, now]);
+        assert_eq!(snapshots[0].projects["/work/app"], 2);
+        // The reset merged into the last group keeps the peak before it
+        let mut reset = vec![snap(now - 30, 0.59, 1), snap(now, 0.0, 1)];
+        compact(&mut reset, &Retention::default(), now);
+        assert_eq!(reset.len(), 1);
+        assert_eq!(reset[0].buckets["Current session"], 0.59);
+    }
+
+    #[test]
+    fn usage_series_aggregates_per_interval() {
+        let hour = 3600;
+        let snapshots = vec![
+            snap(10 * hour - 60, 0.05, 0),
+            snap(10 * hour, 0.1, 0),
+            snap(10 * hour + 600, 0.3, 0),
+            snap(10 * hour + 1200, 0.2, 0),
+            snap(12 * hour + 60, 0.4, 0),
+            snap(20 * hour, 0.9, 0),
+        ];
+        let series = usage_series(&snapshots, "Current session", 10 * hour, 13 * hour, Resolution::Hour, |_| 0);
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].timestamp, 10 * hour);
+        assert_eq!((series[0].min, series[0].max, series[0].samples), (0.1, 0.3, 3));
+        assert!((series[0].avg - 0.2).abs() < 1e-9);
+        assert_eq!(series[1].timestamp, 12 * hour);
+
+        // At UTC-3, days start at 03:00 UTC
+        let day = usage_series(&snapshots, "Current session", 0, 24 * hour, Resolution::Day, |_| -3 * hour);
+        assert_eq!(day.iter().map(|p| (p.timestamp, p.samples)).collect::<Vec<_>>(), vec![(3 * hour, 6)]);
+        assert!(usage_series(&snapshots, "Current week (all models)", 0, 24 * hour, Resolution::FiveMinutes, |_| 0).is_empty());
+    }
+
+    #[test]
+    fn day_intervals_follow_dst_changes() {
+        let (hour, day) = (3600, 24 * 3600);
+        // UTC+1, moving to UTC+2 at 01:00 UTC on day 1
+        let offset = |t: i64| if t < day + hour { hour } else { 2 * hour };
+        let snapshots = vec![snap(day / 2, 0.1, 0), snap(day + 12 * hour, 0.2, 0), snap(2 * day + 12 * hour, 0.3, 0)];
+        let series = usage_series(&snapshots, "Current session", 0, 3 * day, Resolution::Day, offset);
+        // Local midnight is 23:00 UTC before the change and 22:00 after it
+        let starts: Vec<_> = series.iter().map(|p| p.timestamp).collect();
+        assert_eq!(starts, [-hour, day - hour, 2 * day - 2 * hour]);
+    }
+}
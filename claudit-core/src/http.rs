@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// An explicit proxy for every request, with optional basic auth. Without one,
+/// `HTTPS_PROXY` / `HTTP_PROXY` / `NO_PROXY` and the system proxy apply.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// e.g. "http://proxy.corp.example:3128".
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Kept in the keychain rather than with the other settings; never
+    /// serialized, only read to migrate older settings files.
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+}
+
+/// Shared client, so connections (and TLS sessions) are reused across calls,
+/// with the explicit proxy it was built for.
+static CLIENT: Mutex<Option<(Option<ProxyConfig>, reqwest::Client)>> = Mutex::new(None);
+
+fn build(proxy: Option<&ProxyConfig>) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(p) = proxy {
+        let mut proxy = reqwest::Proxy::all(&p.url).map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        if let Some(ref user) = p.username {
+            proxy = proxy.basic_auth(user, p.password.as_deref().unwrap_or(""));
+        }
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Check that `proxy` can be used, without applying it.
+pub fn validate_proxy(proxy: &ProxyConfig) -> Result<(), String> {
+    build(Some(proxy)).map(|_| ())
+}
+
+/// Route all requests through `proxy`, or back to the environment's proxy
+/// settings with `None`. The previous client stays in use if this fails.
+pub fn set_proxy(proxy: Option<&ProxyConfig>) -> Result<(), String> {
+    let mut guard = CLIENT.lock().unwrap();
+    if guard.as_ref().is_some_and(|(current, _)| current.as_ref() == proxy) {
+        return Ok(());
+    }
+    let client = build(proxy)?;
+    crate::log(&format!("http: proxy {}", proxy.map_or("from environment", |p| p.url.as_str())));
+    *guard = Some((proxy.cloned(), client));
+    Ok(())
+}
+
+/// The explicit proxy in use, if any.
+pub fn proxy() -> Option<ProxyConfig> {
+    CLIENT.lock().unwrap().as_ref().and_then(|(proxy, _)| proxy.clone())
+}
+
+/// The shared HTTP client for every network call.
+pub fn client() -> reqwest::Client {
+    let mut guard = CLIENT.lock().unwrap();
+    guard
+        .get_or_insert_with(|| (None, build(None).unwrap_or_else(|_| reqwest::Client::new())))
+        .1
+        .clone()
+}
@@ -0,0 +1,399 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeychainError {
+    #[error("Keychain entry not found. Run `claude` first to authenticate.")]
+    NotFound,
+    #[error("Failed to parse keychain data: {0}")]
+    ParseError(String),
+    #[error("Command failed: {0}")]
+    CommandError(String),
+    #[error("Refusing to read credentials file: {0}")]
+    InsecureFile(String),
+}
+
+const SERVICE: &str = "Claude Code-credentials";
+
+/// Environment variable holding a long-lived token (as from `claude setup-token`).
+/// When set it overrides every stored credential.
+pub const TOKEN_ENV_VAR: &str = "CLAUDE_CODE_OAUTH_TOKEN";
+
+/// Pins the credentials file to this path and skips the keychain entirely.
+/// Only read with the `test-hooks` feature.
+#[cfg(feature = "test-hooks")]
+pub const CREDENTIALS_FILE_ENV_VAR: &str = "CLAUDIT_CREDENTIALS_FILE";
+
+/// Keychain service of Claudit's own Admin API keys, one entry per profile.
+const ADMIN_KEY_SERVICE: &str = "Claudit-admin-api-key";
+
+/// Keychain service of the password for the configured HTTP proxy.
+const PROXY_PASSWORD_SERVICE: &str = "Claudit-proxy-password";
+
+/// Environment variable holding an Admin API key. When set it is used for
+/// every profile instead of the stored keys.
+pub const ADMIN_KEY_ENV_VAR: &str = "CLAUDIT_ADMIN_API_KEY";
+
+/// Where a set of credentials was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialSource {
+    Env,
+    Keychain,
+    File,
+}
+
+/// The parts of Claude Code's `claudeAiOauth` credential blob that Claudit uses.
+#[derive(Debug, Clone)]
+pub struct OAuthCredentials {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Milliseconds since the UNIX epoch.
+    pub expires_at: Option<i64>,
+    pub subscription_type: Option<String>,
+    pub rate_limit_tier: Option<String>,
+    pub source: CredentialSource,
+}
+
+/// Resolve credentials from the first source that has them: the env var
+/// override, then the OS keychain, then Claude Code's credentials file (used on
+/// headless Linux and WSL where no keychain service is running).
+pub fn get_credentials() -> Result<OAuthCredentials, KeychainError> {
+    resolve_credentials(
+        std::env::var(TOKEN_ENV_VAR).ok(),
+        pinned_credentials_file().is_some(),
+        get_raw_credentials,
+        read_credentials_file,
+    )
+}
+
+/// The lookup order of [`get_credentials`], over the given sources. With
+/// `file_pinned` the keychain is skipped.
+fn resolve_credentials(
+    env_token: Option<String>,
+    file_pinned: bool,
+    keychain: impl FnOnce() -> Result<String, KeychainError>,
+    file: impl FnOnce() -> Result<Option<String>, KeychainError>,
+) -> Result<OAuthCredentials, KeychainError> {
+    if let Some(token) = env_token.filter(|t| !t.trim().is_empty()) {
+        return Ok(OAuthCredentials {
+            access_token: token.trim().to_string(),
+            refresh_token: None,
+            expires_at: None,
+            subscription_type: None,
+            rate_limit_tier: None,
+            source: CredentialSource::Env,
+        });
+    }
+
+    if file_pinned {
+        return match file()? {
+            Some(raw) => parse_credentials(&raw, CredentialSource::File),
+            None => Err(KeychainError::NotFound),
+        };
+    }
+
+    match keychain() {
+        Ok(raw) => parse_credentials(&raw, CredentialSource::Keychain),
+        Err(keychain_err) => {
+            crate::log(&format!("keychain: {}, trying credentials file", keychain_err));
+            match file()? {
+                Some(raw) => parse_credentials(&raw, CredentialSource::File),
+                None => Err(keychain_err),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "test-hooks")]
+fn pinned_credentials_file() -> Option<PathBuf> {
+    std::env::var_os(CREDENTIALS_FILE_ENV_VAR).map(PathBuf::from)
+}
+
+#[cfg(not(feature = "test-hooks"))]
+fn pinned_credentials_file() -> Option<PathBuf> {
+    None
+}
+
+/// `$CLAUDE_CONFIG_DIR/.credentials.json`, defaulting to
+/// `~/.claude/.credentials.json`.
+pub fn credentials_file_path() -> Option<PathBuf> {
+    if let Some(path) = pinned_credentials_file() {
+        return Some(path);
+    }
+    std::env::var_os("CLAUDE_CONFIG_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|h| h.join(".claude")))
+        .map(|dir| dir.join(".credentials.json"))
+}
+
+/// Read the credentials file, or `None` if it doesn't exist.
+fn read_credentials_file() -> Result<Option<String>, KeychainError> {
+    let path = match credentials_file_path() {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    let meta = match fs::metadata(&path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(KeychainError::CommandError(e.to_string())),
+    };
+    if !meta.is_file() {
+        return Err(KeychainError::InsecureFile(format!("{} is not a regular file", path.display())));
+    }
+    check_file_permissions(&path, &meta)?;
+    fs::read_to_string(&path)
+        .map(Some)
+        .map_err(|e| KeychainError::CommandError(e.to_string()))
+}
+
+/// The file holds a bearer token, so any group or other permission is refused.
+#[cfg(unix)]
+fn check_file_permissions(path: &Path, meta: &fs::Metadata) -> Result<(), KeychainError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = meta.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        return Err(KeychainError::InsecureFile(format!(
+            "{} is accessible to users other than its owner (mode {:o}); run `chmod 600 {}`",
+            path.display(),
+            mode,
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_file_permissions(_path: &Path, _meta: &fs::Metadata) -> Result<(), KeychainError> {
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn get_raw_credentials() -> Result<String, KeychainError> {
+    let output = std::process::Command::new("security")
+        .args(["find-generic-password", "-s", SERVICE, "-w"])
+        .output()
+        .map_err(|e| KeychainError::CommandError(e.to_string()))?;
+
+    if !output.status.success() {
+        return Err(KeychainError::NotFound);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn get_raw_credentials() -> Result<String, KeychainError> {
+    let entry = keyring::Entry::new(SERVICE, "default")
+        .map_err(|e| KeychainError::CommandError(e.to_string()))?;
+
+    entry
+        .get_password()
+        .map_err(|e| match e {
+            keyring::Error::NoEntry => KeychainError::NotFound,
+            _ => KeychainError::CommandError(e.to_string()),
+        })
+}
+
+/// Write refreshed tokens back to wherever the credentials came from, patching
+/// only the token fields so everything else Claude Code keeps in the blob survives.
+pub fn store_refreshed(
+    source: CredentialSource,
+    access_token: &str,
+    refresh_token: Option<&str>,
+    expires_at: i64,
+) -> Result<(), KeychainError> {
+    let raw = match source {
+        // Nothing stored to update; the override is the user's to manage
+        CredentialSource::Env => return Ok(()),
+        CredentialSource::Keychain => get_raw_credentials()?,
+        CredentialSource::File => read_credentials_file()?.ok_or(KeychainError::NotFound)?,
+    };
+
+    let mut creds: serde_json::Value = serde_json::from_str(&raw)
+        .map_err(|e| KeychainError::ParseError(e.to_string()))?;
+    let oauth = creds
+        .get_mut("claudeAiOauth")
+        .and_then(|v| v.as_object_mut())
+        .ok_or_else(|| KeychainError::ParseError("Missing claudeAiOauth".into()))?;
+    oauth.insert("accessToken".into(), access_token.into());
+    if let Some(rt) = refresh_token {
+        oauth.insert("refreshToken".into(), rt.into());
+    }
+    oauth.insert("expiresAt".into(), expires_at.into());
+    let updated = creds.to_string();
+
+    match source {
+        CredentialSource::Keychain => set_raw_credentials(&updated),
+        _ => {
+            let path = credentials_file_path().ok_or(KeychainError::NotFound)?;
+            crate::storage::write_atomic(&path, &updated)
+                .map_err(|e| KeychainError::CommandError(e.to_string()))
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn set_raw_credentials(raw: &str) -> Result<(), KeychainError> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let account = std::env::var("USER").unwrap_or_default();
+    let hex: String = raw.bytes().map(|b| format!("{:02x}", b)).collect();
+
+    // `security -i` reads the command from stdin, keeping the secret out of argv
+    let mut child = std::process::Command::new("security")
+        .arg("-i")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| KeychainError::CommandError(e.to_string()))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "add-generic-password -U -a \"{}\" -s \"{}\" -X \"{}\"", account, SERVICE, hex)
+            .map_err(|e| KeychainError::CommandError(e.to_string()))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| KeychainError::CommandError(e.to_string()))?;
+    if !output.status.success() {
+        return Err(KeychainError::CommandError(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn set_raw_credentials(raw: &str) -> Result<(), KeychainError> {
+    keyring::Entry::new(SERVICE, "default")
+        .and_then(|entry| entry.set_password(raw))
+        .map_err(|e| KeychainError::CommandError(e.to_string()))
+}
+
+/// The Admin API key stored for `profile`, or the env var override.
+pub fn admin_api_key(profile: &str) -> Result<String, KeychainError> {
+    if let Some(key) = std::env::var(ADMIN_KEY_ENV_VAR).ok().filter(|k| !k.trim().is_empty()) {
+        return Ok(key.trim().to_string());
+    }
+    // Claudit's own entry, so keyring can manage it on every platform
+    keyring::Entry::new(ADMIN_KEY_SERVICE, profile)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| match e {
+            keyring::Error::NoEntry => KeychainError::NotFound,
+            _ => KeychainError::CommandError(e.to_string()),
+        })
+}
+
+/// Store `key` as the Admin API key for `profile`, or remove it with `None`.
+pub fn set_admin_api_key(profile: &str, key: Option<&str>) -> Result<(), KeychainError> {
+    let entry = keyring::Entry::new(ADMIN_KEY_SERVICE, profile)
+        .map_err(|e| KeychainError::CommandError(e.to_string()))?;
+    let result = match key {
+        Some(key) => entry.set_password(key.trim()),
+        None => match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        },
+    };
+    result.map_err(|e| KeychainError::CommandError(e.to_string()))
+}
+
+/// The stored password for the HTTP proxy.
+pub fn proxy_password() -> Result<String, KeychainError> {
+    keyring::Entry::new(PROXY_PASSWORD_SERVICE, "default")
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| match e {
+            keyring::Error::NoEntry => KeychainError::NotFound,
+            _ => KeychainError::CommandError(e.to_string()),
+        })
+}
+
+/// Store `password` for the HTTP proxy, or remove it with `None`.
+pub fn set_proxy_password(password: Option<&str>) -> Result<(), KeychainError> {
+    let entry = keyring::Entry::new(PROXY_PASSWORD_SERVICE, "default")
+        .map_err(|e| KeychainError::CommandError(e.to_string()))?;
+    let result = match password {
+        Some(password) => entry.set_password(password),
+        None => match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        },
+    };
+    result.map_err(|e| KeychainError::CommandError(e.to_string()))
+}
+
+fn parse_credentials(raw: &str, source: CredentialSource) -> Result<OAuthCredentials, KeychainError> {
+    let creds: serde_json::Value = serde_json::from_str(raw)
+        .map_err(|e| KeychainError::ParseError(e.to_string()))?;
+
+    let oauth = creds
+        .get("claudeAiOauth")
+        .ok_or_else(|| KeychainError::ParseError("Missing claudeAiOauth".into()))?;
+
+    let token = oauth
+        .get("accessToken")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| KeychainError::ParseError("Missing claudeAiOauth.accessToken".into()))?;
+
+    let string_field = |key: &str| oauth.get(key).and_then(|v| v.as_str()).map(String::from);
+
+    Ok(OAuthCredentials {
+        access_token: token.to_string(),
+        refresh_token: string_field("refreshToken"),
+        expires_at: oauth.get("expiresAt").and_then(|v| v.as_i64()),
+        subscription_type: string_field("subscriptionType"),
+        rate_limit_tier: string_field("rateLimitTier"),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOB: &str = r#"{"claudeAiOauth":{"accessToken":"from-store","refreshToken":"r","expiresAt":1}}"#;
+
+    fn no_keychain() -> Result<String, KeychainError> {
+        Err(KeychainError::NotFound)
+    }
+
+    #[test]
+    fn credentials_come_from_the_first_source_that_has_them() {
+        let keychain = || Ok(BLOB.to_string());
+        let file = || Ok(Some(BLOB.to_string()));
+        let unused_file = || -> Result<Option<String>, KeychainError> { panic!("file read") };
+
+        let env = resolve_credentials(Some(" sk-env ".into()), false, keychain, unused_file).unwrap();
+        assert_eq!((env.access_token.as_str(), env.source), ("sk-env", CredentialSource::Env));
+
+        // A blank override is ignored
+        let stored = resolve_credentials(Some("  ".into()), false, keychain, unused_file).unwrap();
+        assert_eq!(stored.source, CredentialSource::Keychain);
+        assert_eq!(stored.refresh_token.as_deref(), Some("r"));
+
+        let fallback = resolve_credentials(None, false, no_keychain, file).unwrap();
+        assert_eq!(fallback.source, CredentialSource::File);
+
+        let pinned = resolve_credentials(None, true, || panic!("keychain read"), file).unwrap();
+        assert_eq!(pinned.source, CredentialSource::File);
+
+        let missing = resolve_credentials(None, false, no_keychain, || Ok(None));
+        assert!(matches!(missing, Err(KeychainError::NotFound)));
+        let insecure = resolve_credentials(None, false, no_keychain, || {
+            Err(KeychainError::InsecureFile("mode 644".into()))
+        });
+        assert!(matches!(insecure, Err(KeychainError::InsecureFile(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_owner_only_files_are_accepted() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join(format!("claudit-perm-test-{}", std::process::id()));
+        fs::write(&path, BLOB).unwrap();
+        for (mode, ok) in [(0o600, true), (0o400, true), (0o640, false), (0o604, false), (0o620, false)] {
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+            let meta = fs::metadata(&path).unwrap();
+            assert_eq!(check_file_permissions(&path, &meta).is_ok(), ok, "mode {:o}", mode);
+        }
+        let _ = fs::remove_file(&path);
+    }
+}
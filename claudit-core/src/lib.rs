@@ -0,0 +1,41 @@
+//! Claude usage monitoring without a UI: OAuth credentials from Claude Code's
+//! keychain entry or credentials file, the usage-limits API, costs via
+//! ccusage, and a local usage history with burn-rate and forecast analytics.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), claudit_core::usage_api::UsageError> {
+//! let usage = claudit_core::token::fetch_usage().await?;
+//! for limit in &usage.limits {
+//!     println!("{}: {:.0}%", limit.label, limit.usage_pct * 100.0);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod burn_rate;
+pub mod ccusage;
+pub mod exhaustion;
+pub mod forecast;
+pub mod history;
+pub mod http;
+pub mod keychain;
+pub mod projects;
+pub mod sessions;
+pub mod storage;
+pub mod token;
+pub mod usage_api;
+
+use std::sync::OnceLock;
+
+static LOGGER: OnceLock<fn(&str)> = OnceLock::new();
+
+/// Route the crate's diagnostic messages to `logger`. Without one they are dropped.
+pub fn set_logger(logger: fn(&str)) {
+    let _ = LOGGER.set(logger);
+}
+
+pub(crate) fn log(msg: &str) {
+    if let Some(logger) = LOGGER.get() {
+        logger(msg);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::log;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Claude Code's per-project transcript directory: `~/.claude/projects`, or
+/// `$CLAUDE_CONFIG_DIR/projects` when that is set.
+pub fn projects_dir() -> Option<PathBuf> {
+    std::env::var_os("CLAUDE_CONFIG_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|h| h.join(".claude")))
+        .map(|dir| dir.join("projects"))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscriptLine {
+    timestamp: Option<String>,
+    cwd: Option<String>,
+    session_id: Option<String>,
+    request_id: Option<String>,
+    #[serde(rename = "costUSD")]
+    cost_usd: Option<f64>,
+    message: Option<TranscriptMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TranscriptMessage {
+    id: Option<String>,
+    model: Option<String>,
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Cache reads are left out: they are cheap against the limits and would
+    /// otherwise swamp every other kind of token.
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_creation_input_tokens
+    }
+}
+
+/// The token usage of one model response in a transcript.
+#[derive(Debug, Clone)]
+pub struct UsageEntry {
+    /// Unix seconds.
+    pub timestamp: i64,
+    pub cwd: Option<String>,
+    pub session_id: Option<String>,
+    pub model: Option<String>,
+    /// As recorded by Claude Code, in USD, which older versions did.
+    pub cost_usd: Option<f64>,
+    pub usage: TokenUsage,
+}
+
+/// Message and request IDs already counted, with the time of each message.
+/// Claude Code logs a response once per content block, and a resumed session
+/// copies the earlier messages into its new transcript, so the same usage
+/// turns up more than once.
+pub type Seen = HashMap<String, i64>;
+
+/// Call `f` with each usage entry in the complete lines of `reader` that
+/// isn't in `seen` yet, and return the number of bytes those lines take up.
+/// A trailing line without a newline is still being written and is left for
+/// the next read.
+pub fn read_usage(mut reader: impl BufRead, seen: &mut Seen, mut f: impl FnMut(UsageEntry)) -> u64 {
+    let mut consumed = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(n) if n > 0 && line.ends_with(b"\n") => consumed += n as u64,
+            _ => return consumed,
+        }
+        let entry: TranscriptLine = match serde_json::from_slice(&line) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let Some(TranscriptMessage { id, model, usage: Some(usage) }) = entry.message else {
+            continue;
+        };
+        let Some(timestamp) = entry
+            .timestamp
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.timestamp())
+        else {
+            continue;
+        };
+        if let (Some(id), Some(request)) = (id, entry.request_id) {
+            if seen.insert(format!("{}:{}", id, request), timestamp).is_some() {
+                continue;
+            }
+        }
+        f(UsageEntry {
+            timestamp,
+            cwd: entry.cwd,
+            session_id: entry.session_id,
+            model,
+            cost_usd: entry.cost_usd,
+            usage,
+        });
+    }
+}
+
+/// Tokens used over a period, per project and per model.
+#[derive(Debug, Clone, Default)]
+pub struct Activity {
+    /// Keyed by the project's working directory.
+    pub projects: HashMap<String, u64>,
+    pub models: HashMap<String, u64>,
+}
+
+/// Reads the transcripts incrementally: each call to
+/// [`activity_between`](Self::activity_between) picks up where the last one
+/// stopped in every file, so a poll only parses what was appended since.
+#[derive(Debug, Default)]
+pub struct ActivityTracker {
+    offsets: HashMap<PathBuf, u64>,
+    seen: Seen,
+}
+
+impl ActivityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tokens used between `since` and `until`, in Unix seconds, in the
+    /// transcript lines under `dir` that earlier calls haven't read.
+    pub fn activity_between(&mut self, dir: &Path, since: i64, until: i64) -> Activity {
+        let mut totals = Activity::default();
+        // Repeats carry the original message's time, so older IDs can't match
+        // anything this call counts
+        self.seen.retain(|_, ts| *ts >= since);
+        let project_dirs = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return totals,
+        };
+        let modified_since = UNIX_EPOCH + Duration::from_secs(since.max(0) as u64);
+
+        for project in project_dirs.flatten() {
+            let files = match fs::read_dir(project.path()) {
+                Ok(f) => f,
+                Err(_) => continue,
+            };
+            let fallback_name = project.file_name().to_string_lossy().to_string();
+            for file in files.flatten() {
+                let path = file.path();
+                if path.extension().is_none_or(|e| e != "jsonl") {
+                    continue;
+                }
+                let Ok(meta) = file.metadata() else {
+                    continue;
+                };
+                // Transcripts are append-only, so untouched files have nothing new
+                if meta.modified().is_ok_and(|m| m < modified_since) {
+                    continue;
+                }
+                let offset = self.offsets.get(&path).copied().filter(|o| *o <= meta.len()).unwrap_or(0);
+                if offset == meta.len() {
+                    continue;
+                }
+                let read = self.add_file(&path, offset, &fallback_name, since, until, &mut totals);
+                self.offsets.insert(path, offset + read);
+            }
+        }
+        totals
+    }
+
+    fn add_file(&mut self, path: &Path, offset: u64, fallback_name: &str, since: i64, until: i64, totals: &mut Activity) -> u64 {
+        let mut file = match fs::File::open(path) {
+            Ok(f) => f,
+            Err(e) => {
+                log(&format!("projects: can't read {}: {}", path.display(), e));
+                return 0;
+            }
+        };
+        if let Err(e) = file.seek(SeekFrom::Start(offset)) {
+            log(&format!("projects: can't read {}: {}", path.display(), e));
+            return 0;
+        }
+        read_usage(BufReader::new(file), &mut self.seen, |entry| {
+            if entry.timestamp < since || entry.timestamp >= until {
+                return;
+            }
+            let tokens = entry.usage.total();
+            let project = entry.cwd.unwrap_or_else(|| fallback_name.to_string());
+            *totals.projects.entry(project).or_default() += tokens;
+            if let Some(model) = entry.model {
+                *totals.models.entry(model).or_default() += tokens;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(id: &str, request: &str, time: &str, input: u64) -> String {
+        format!(
+            r#"{{"timestamp":"2026-03-02T{}:00Z","cwd":"/work/app","requestId":"{}","message":{{"id":"{}","model":"claude-sonnet-4-5","usage":{{"input_tokens":{},"output_tokens":0}}}}}}"#,
+            time, request, id, input
+        ) + "\n"
+    }
+
+    fn at(time: &str) -> i64 {
+        chrono::DateTime::parse_from_rfc3339(&format!("2026-03-02T{}:00Z", time)).unwrap().timestamp()
+    }
+
+    #[test]
+    fn repeated_messages_and_partial_lines_are_skipped() {
+        let complete = line("m1", "r1", "10:00", 100) + &line("m1", "r1", "10:00", 100) + &line("m2", "r2", "10:01", 5);
+        let text = complete.clone() + r#"{"timestamp":"2026-03-02T10:02"#;
+        let mut tokens = Vec::new();
+        let read = read_usage(text.as_bytes(), &mut Seen::new(), |e| tokens.push(e.usage.total()));
+        assert_eq!(tokens, [100, 5]);
+        assert_eq!(read, complete.len() as u64);
+    }
+
+    #[test]
+    fn tracker_reads_only_what_was_appended() {
+        let dir = std::env::temp_dir().join(format!("claudit-projects-test-{}", std::process::id()));
+        let project = dir.join("-work-app");
+        fs::create_dir_all(&project).unwrap();
+        let path = project.join("s-1.jsonl");
+        let mut tracker = ActivityTracker::new();
+
+        fs::write(&path, line("m1", "r1", "10:00", 100)).unwrap();
+        let first = tracker.activity_between(&dir, at("09:00"), at("11:00"));
+        assert_eq!(first.projects["/work/app"], 100);
+
+        // A resumed session repeats m1 in a new transcript
+        let resumed = line("m1", "r1", "10:00", 100) + &line("m3", "r3", "10:30", 7);
+        fs::write(project.join("s-2.jsonl"), &resumed).unwrap();
+        fs::write(&path, line("m1", "r1", "10:00", 100) + &line("m2", "r2", "10:20", 20)).unwrap();
+        let second = tracker.activity_between(&dir, at("09:00"), at("11:00"));
+        assert_eq!(second.projects["/work/app"], 27);
+        assert_eq!(second.models["claude-sonnet-4-5"], 27);
+
+        assert!(tracker.activity_between(&dir, at("09:00"), at("11:00")).projects.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+use crate::log;
+use crate::projects::{self, Seen, TokenUsage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// One Claude Code session, from its transcript.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    /// The session's working directory.
+    pub project: String,
+    /// First and last message, Unix seconds.
+    pub start: i64,
+    pub end: i64,
+    pub duration_secs: i64,
+    /// Tokens per model, cache reads included.
+    pub models: HashMap<String, u64>,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub total_tokens: u64,
+    /// Estimated from list prices, in USD, unless the transcript records it.
+    /// Leaves out the tokens of `unpriced_models`.
+    pub cost: f64,
+    /// Models used in the session whose price isn't known, so `cost` is a
+    /// lower bound when this isn't empty.
+    pub unpriced_models: Vec<String>,
+}
+
+/// Order of `get_sessions` results, largest or latest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    #[default]
+    Recent,
+    Cost,
+    Tokens,
+    Duration,
+}
+
+/// A slice of the sorted sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPage {
+    pub sessions: Vec<Session>,
+    /// Sessions across all pages.
+    pub total: usize,
+    pub offset: usize,
+}
+
+/// USD per million input, output, cache-write and cache-read tokens.
+type Prices = (f64, f64, f64, f64);
+
+/// List prices by model family, or `None` for models we don't know.
+fn prices(model: &str) -> Option<Prices> {
+    let m = model.to_lowercase();
+    let prices = if m.contains("opus") {
+        if ["opus-4-5", "opus-4-6"].iter().any(|v| m.contains(v)) {
+            (5.0, 25.0, 6.25, 0.5)
+        } else {
+            (15.0, 75.0, 18.75, 1.5)
+        }
+    } else if m.contains("sonnet") {
+        (3.0, 15.0, 3.75, 0.3)
+    } else if m.contains("haiku") {
+        if m.contains("3-5") {
+            (0.8, 4.0, 1.0, 0.08)
+        } else if m.contains("3-haiku") {
+            (0.25, 1.25, 0.3, 0.03)
+        } else {
+            (1.0, 5.0, 1.25, 0.1)
+        }
+    } else {
+        return None;
+    };
+    Some(prices)
+}
+
+fn estimate_cost(model: &str, usage: &TokenUsage) -> Option<f64> {
+    let (input, output, cache_write, cache_read) = prices(model)?;
+    Some(
+        (usage.input_tokens as f64 * input
+            + usage.output_tokens as f64 * output
+            + usage.cache_creation_input_tokens as f64 * cache_write
+            + usage.cache_read_input_tokens as f64 * cache_read)
+            / 1_000_000.0,
+    )
+}
+
+/// Sessions with messages since `since` (Unix seconds), from the transcripts
+/// under `dir`, in no particular order.
+pub fn sessions_since(dir: &Path, since: i64) -> Vec<Session> {
+    let mut sessions = HashMap::new();
+    let mut seen = Seen::new();
+    let project_dirs = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let modified_since = UNIX_EPOCH + Duration::from_secs(since.max(0) as u64);
+
+    for project in project_dirs.flatten() {
+        let files = match fs::read_dir(project.path()) {
+            Ok(f) => f,
+            Err(_) => continue,
+        };
+        let fallback_project = project.file_name().to_string_lossy().to_string();
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().is_none_or(|e| e != "jsonl") {
+                continue;
+            }
+            if file.metadata().and_then(|m| m.modified()).is_ok_and(|m| m < modified_since) {
+                continue;
+            }
+            let reader = match fs::File::open(&path) {
+                Ok(f) => BufReader::new(f),
+                Err(e) => {
+                    log(&format!("sessions: can't read {}: {}", path.display(), e));
+                    continue;
+                }
+            };
+            let fallback_id = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            add_transcript(reader, &fallback_id, &fallback_project, since, &mut sessions, &mut seen);
+        }
+    }
+    sessions.into_values().collect()
+}
+
+/// Add the messages in one transcript to `sessions`, skipping those already
+/// in `seen` (see [`projects::Seen`]).
+fn add_transcript(
+    reader: impl BufRead,
+    fallback_id: &str,
+    fallback_project: &str,
+    since: i64,
+    sessions: &mut HashMap<String, Session>,
+    seen: &mut Seen,
+) {
+    projects::read_usage(reader, seen, |entry| {
+        let ts = entry.timestamp;
+        if ts < since {
+            return;
+        }
+        let usage = entry.usage;
+
+        let session_id = entry.session_id.unwrap_or_else(|| fallback_id.to_string());
+        let session = sessions.entry(session_id.clone()).or_insert_with(|| Session {
+            id: session_id,
+            project: fallback_project.to_string(),
+            start: ts,
+            end: ts,
+            duration_secs: 0,
+            models: HashMap::new(),
+            input_tokens: 0,
+            output_tokens: 0,
+            cache_creation_tokens: 0,
+            cache_read_tokens: 0,
+            total_tokens: 0,
+            cost: 0.0,
+            unpriced_models: Vec::new(),
+        });
+        if let Some(cwd) = entry.cwd {
+            session.project = cwd;
+        }
+        session.start = session.start.min(ts);
+        session.end = session.end.max(ts);
+        session.duration_secs = session.end - session.start;
+
+        let tokens = usage.input_tokens
+            + usage.output_tokens
+            + usage.cache_creation_input_tokens
+            + usage.cache_read_input_tokens;
+        session.input_tokens += usage.input_tokens;
+        session.output_tokens += usage.output_tokens;
+        session.cache_creation_tokens += usage.cache_creation_input_tokens;
+        session.cache_read_tokens += usage.cache_read_input_tokens;
+        session.total_tokens += tokens;
+        let model = entry.model.unwrap_or_default();
+        // Claude Code logs API errors and interrupts as "<synthetic>" messages
+        // with no usage; they cost nothing and have no price to look up
+        if tokens == 0 || model.is_empty() || model == "<synthetic>" {
+            session.cost += entry.cost_usd.unwrap_or(0.0);
+            return;
+        }
+        match entry.cost_usd.or_else(|| estimate_cost(&model, &usage)) {
+            Some(cost) => session.cost += cost,
+            None if !session.unpriced_models.contains(&model) => session.unpriced_models.push(model.clone()),
+            None => {}
+        }
+        *session.models.entry(model).or_default() += tokens;
+    });
+}
+
+/// `limit` sessions from `offset` after sorting by `sort`.
+pub fn page(mut sessions: Vec<Session>, sort: SessionSort, offset: usize, limit: usize) -> SessionPage {
+    sessions.sort_by(|a, b| {
+        let order = match sort {
+            SessionSort::Recent => b.end.cmp(&a.end),
+            SessionSort::Cost => b.cost.total_cmp(&a.cost),
+            SessionSort::Tokens => b.total_tokens.cmp(&a.total_tokens),
+            SessionSort::Duration => b.duration_secs.cmp(&a.duration_secs),
+        };
+        order.then(b.end.cmp(&a.end))
+    });
+    let total = sessions.len();
+    let sessions = sessions.into_iter().skip(offset).take(limit).collect();
+    SessionPage { sessions, total, offset }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcript_lines_add_up_per_session() {
+        let transcript = include_str!("../tests/fixtures/sessions/transcript.jsonl");
+        let mut sessions = HashMap::new();
+        add_transcript(transcript.as_bytes(), "file-stem", "-work-app", 0, &mut sessions, &mut Seen::new());
+
+        assert_eq!(sessions.len(), 2);
+        let main = &sessions["s-1"];
+        assert_eq!(main.project, "/work/app");
+        assert_eq!(main.duration_secs, 600);
+        // The repeated message counts once
+        assert_eq!((main.input_tokens, main.output_tokens, main.cache_read_tokens), (1100, 2000, 10_000));
+        assert_eq!(main.models["claude-sonnet-4-5-20250929"], 3100);
+        assert_eq!(main.models["claude-opus-4-1-20250805"], 10_000);
+        // 1000 Sonnet input + 2000 output, then 10k Opus cache reads
+        let expected = (1000.0 * 3.0 + 2000.0 * 15.0 + 100.0 * 3.0 + 10_000.0 * 1.5) / 1_000_000.0;
+        assert!((main.cost - expected).abs() < 1e-9);
+        assert!(main.unpriced_models.is_empty());
+        // Recorded cost wins over the estimate, and unknown models add nothing
+        assert_eq!(sessions["s-2"].cost, 0.25);
+        // The zero-usage "<synthetic>" message isn't an unpriced model
+        assert_eq!(sessions["s-2"].unpriced_models, ["claude-nova-1"]);
+        assert_eq!(sessions["s-2"].end - sessions["s-2"].start, 120);
+
+        let page = page(sessions.into_values().collect(), SessionSort::Tokens, 0, 1);
+        assert_eq!((page.total, page.sessions[0].id.as_str()), (2, "s-1"));
+    }
+
+    #[test]
+    fn haiku_generations_have_their_own_prices() {
+        assert_eq!(prices("claude-3-haiku-20240307"), Some((0.25, 1.25, 0.3, 0.03)));
+        assert_eq!(prices("claude-3-5-haiku-20241022"), Some((0.8, 4.0, 1.0, 0.08)));
+        assert_eq!(prices("claude-haiku-4-5-20251001"), Some((1.0, 5.0, 1.25, 0.1)));
+        let usage = TokenUsage { input_tokens: 1_000_000, output_tokens: 1_000_000, ..Default::default() };
+        assert_eq!(estimate_cost("claude-3-haiku-20240307", &usage), Some(1.5));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::log;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Writes that are currently failing, one entry per store.
+static FAILURES: Mutex<Vec<WriteFailure>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    DiskFull,
+    ReadOnly,
+    PermissionDenied,
+    Other,
+}
+
+impl FailureKind {
+    fn of(e: &io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::StorageFull => FailureKind::DiskFull,
+            io::ErrorKind::ReadOnlyFilesystem => FailureKind::ReadOnly,
+            io::ErrorKind::PermissionDenied => FailureKind::PermissionDenied,
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteFailure {
+    /// Which file is affected, e.g. "history" or "settings".
+    pub store: String,
+    pub kind: FailureKind,
+    pub error: String,
+    /// First failure in the current streak, Unix seconds.
+    pub since: i64,
+    pub last_attempt_at: i64,
+    pub attempts: u32,
+}
+
+
+/// Set restrictive file permissions (0600) on Unix systems.
+#[cfg(unix)]
+pub fn set_owner_only_perms(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+}
+
+#[cfg(not(unix))]
+pub fn set_owner_only_perms(_path: &Path) {}
+
+/// Atomic write: write to a temp file next to `path`, then rename over it.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    let result = fs::write(&tmp_path, contents).and_then(|()| {
+        set_owner_only_perms(&tmp_path);
+        fs::rename(&tmp_path, path)
+    });
+    if result.is_err() {
+        // Don't leave a partial temp file behind on a full disk
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// [`write_atomic`], with the outcome recorded against `store` so a failing
+/// disk shows up in [`write_failures`] until a later write succeeds.
+pub fn write_tracked(store: &str, path: &Path, contents: &str) -> io::Result<()> {
+    let result = write_atomic(path, contents);
+    record_write(store, &result);
+    result
+}
+
+pub fn record_write(store: &str, result: &io::Result<()>) {
+    let mut failures = FAILURES.lock().unwrap();
+    let existing = failures.iter().position(|f| f.store == store);
+    match (result, existing) {
+        (Ok(()), Some(i)) => {
+            log(&format!("storage: {} writable again", store));
+            failures.remove(i);
+        }
+        (Ok(()), None) => {}
+        (Err(e), Some(i)) => {
+            let f = &mut failures[i];
+            f.kind = FailureKind::of(e);
+            f.error = e.to_string();
+            f.last_attempt_at = chrono::Utc::now().timestamp();
+            f.attempts += 1;
+        }
+        (Err(e), None) => {
+            let now = chrono::Utc::now().timestamp();
+            log(&format!("storage: {} write failing: {}", store, e));
+            failures.push(WriteFailure {
+                store: store.to_string(),
+                kind: FailureKind::of(e),
+                error: e.to_string(),
+                since: now,
+                last_attempt_at: now,
+                attempts: 1,
+            });
+        }
+    }
+}
+
+/// Stores whose last write failed.
+pub fn write_failures() -> Vec<WriteFailure> {
+    FAILURES.lock().unwrap().clone()
+}
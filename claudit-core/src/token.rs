@@ -0,0 +1,122 @@
+use crate::keychain::{self, OAuthCredentials};
+use crate::log;
+use crate::ccusage::CostData;
+use crate::usage_api::{self, Backend, UsageData, UsageError, UsageSource};
+
+/// Refresh this long before `expiresAt` so in-flight requests don't race expiry.
+const EXPIRY_MARGIN_MS: i64 = 5 * 60 * 1000;
+
+/// Only one refresh at a time: refresh tokens rotate, so a second concurrent
+/// refresh with the same token would be rejected.
+static REFRESH_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+async fn load_credentials() -> Result<OAuthCredentials, UsageError> {
+    tokio::task::spawn_blocking(keychain::get_credentials)
+        .await
+        .map_err(|e| UsageError::Credentials(e.to_string()))?
+        .map_err(|e| UsageError::Credentials(e.to_string()))
+}
+
+fn is_expiring(creds: &OAuthCredentials) -> bool {
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    creds.expires_at.is_some_and(|exp| exp - now_ms < EXPIRY_MARGIN_MS)
+}
+
+/// A usable access token, refreshed first if the stored one is (nearly) expired.
+/// If the refresh fails the stored token is returned as-is and the API decides.
+pub async fn access_token() -> Result<String, UsageError> {
+    Ok(access_token_from(load_credentials().await?).await)
+}
+
+/// Like [`access_token`], for credentials the caller has already read, so
+/// the keychain is only asked again when a refresh is needed.
+pub async fn access_token_from(creds: OAuthCredentials) -> String {
+    if !is_expiring(&creds) || creds.refresh_token.is_none() {
+        return creds.access_token;
+    }
+    match refresh(&creds.access_token).await {
+        Ok(token) => token,
+        Err(e) => {
+            log(&format!("token: refresh failed: {}", e));
+            creds.access_token
+        }
+    }
+}
+
+/// Refresh the stored credentials and write them back. If the stored token no
+/// longer matches `stale`, another task (or the CLI) already refreshed it and
+/// that newer token is returned instead.
+async fn refresh(stale: &str) -> Result<String, UsageError> {
+    let _guard = REFRESH_LOCK.lock().await;
+
+    let creds = load_credentials().await?;
+    if creds.access_token != stale {
+        return Ok(creds.access_token);
+    }
+    let refresh_token = creds.refresh_token.ok_or(UsageError::Unauthorized)?;
+
+    log("token: refreshing access token");
+    let resp = usage_api::refresh_access_token(&refresh_token).await?;
+    let expires_at = chrono::Utc::now().timestamp_millis() + resp.expires_in.unwrap_or(3600) * 1000;
+
+    let source = creds.source;
+    let access = resp.access_token.clone();
+    let new_refresh = resp.refresh_token;
+    let stored = tokio::task::spawn_blocking(move || {
+        keychain::store_refreshed(source, &access, new_refresh.as_deref(), expires_at)
+    })
+    .await;
+    match stored {
+        Ok(Ok(())) => log(&format!("token: refreshed, stored to {:?}", source)),
+        Ok(Err(e)) => log(&format!("token: refreshed but failed to store: {}", e)),
+        Err(e) => log(&format!("token: store task failed: {}", e)),
+    }
+
+    Ok(resp.access_token)
+}
+
+/// Fetch usage with a valid token, refreshing and retrying once on a 401.
+pub async fn fetch_usage() -> Result<UsageData, UsageError> {
+    let token = access_token().await?;
+    match usage_api::fetch_usage(&token).await {
+        Err(UsageError::Unauthorized) => {
+            log("token: usage unauthorized, refreshing and retrying");
+            let fresh = refresh(&token).await.map_err(|e| {
+                log(&format!("token: refresh after 401 failed: {}", e));
+                UsageError::Unauthorized
+            })?;
+            usage_api::fetch_usage(&fresh).await
+        }
+        other => other,
+    }
+}
+
+async fn admin_key(profile: &str) -> Result<String, UsageError> {
+    let name = profile.to_string();
+    tokio::task::spawn_blocking(move || keychain::admin_api_key(&name))
+        .await
+        .map_err(|e| UsageError::Credentials(e.to_string()))?
+        .map_err(|e| match e {
+            keychain::KeychainError::NotFound => {
+                UsageError::Credentials(format!("No Admin API key stored for profile \"{}\"", profile))
+            }
+            e => UsageError::Credentials(e.to_string()),
+        })
+}
+
+/// Fetch usage from `source`'s backend.
+pub async fn fetch_usage_from(source: &UsageSource) -> Result<UsageData, UsageError> {
+    match source.backend {
+        Backend::ClaudeCode => fetch_usage().await,
+        Backend::AdminApi { ref budgets } => {
+            let key = admin_key(&source.profile).await?;
+            usage_api::fetch_admin_usage(&key, budgets).await
+        }
+    }
+}
+
+/// Fetch costs from the Admin API with the key stored for `profile`.
+pub async fn fetch_admin_costs(profile: &str) -> Result<CostData, UsageError> {
+    let key = admin_key(profile).await?;
+    usage_api::fetch_admin_costs(&key).await
+}
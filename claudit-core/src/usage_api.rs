@@ -0,0 +1,798 @@
+use crate::ccusage::CostData;
+use chrono::{DateTime, Datelike, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, Ordering};
+
+#[derive(Debug, thiserror::Error)]
+pub enum UsageError {
+    #[error("HTTP request failed: {0}")]
+    RequestError(String),
+    #[error("Unauthorized - run `claude` to refresh your session")]
+    Unauthorized,
+    #[error("Rate limited - using cached data")]
+    RateLimited,
+    #[error("Failed to parse response: {0}")]
+    ParseError(String),
+    #[error("{0}")]
+    Credentials(String),
+}
+
+const DEFAULT_API_BASE: &str = "https://api.anthropic.com";
+const DEFAULT_OAUTH_TOKEN_URL: &str = "https://console.anthropic.com/v1/oauth/token";
+/// Points every Anthropic request at another host, e.g. a local mock server in
+/// tests. Only read with the `test-hooks` feature, since the requests carry
+/// the user's tokens and keys.
+#[cfg(feature = "test-hooks")]
+pub const API_BASE_ENV_VAR: &str = "CLAUDIT_API_BASE_URL";
+/// Public OAuth client ID of Claude Code, whose refresh tokens we reuse.
+const OAUTH_CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
+
+/// Response layouts of `/api/oauth/usage` that we know how to read.
+///
+/// The endpoint is undocumented and has changed shape before, so parsing is
+/// routed through [`detect_schema`] instead of a single fixed struct. When a new
+/// layout shows up, add a variant here, teach `detect_schema` to recognise it
+/// and give it its own arm in [`parse_usage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaVersion {
+    /// Flat bucket objects (`five_hour`, `seven_day`, ...) at the top level,
+    /// with the plan optionally in `plan`, `tier` or `membership`.
+    V1,
+}
+
+/// Top-level keys that identify a V1 response. At least one must be present.
+const V1_BUCKET_KEYS: &[&str] = &["five_hour", "seven_day", "seven_day_opus", "seven_day_sonnet"];
+
+/// Cooldown after a 429 that came without a usable Retry-After.
+const DEFAULT_COOLDOWN_SECS: i64 = 300;
+/// Longest cooldown we accept, whatever Retry-After asks for, so a bogus
+/// header can't stop polling for days.
+const MAX_COOLDOWN_SECS: i64 = 3600;
+/// Longer Retry-After waits end the request and start a cooldown instead of
+/// blocking the caller.
+const MAX_INLINE_WAIT_SECS: u64 = 10;
+
+/// Unix seconds until which usage requests are not sent, after a 429.
+static COOLDOWN_UNTIL: AtomicI64 = AtomicI64::new(0);
+
+/// End of the current rate-limit cooldown, if one is running.
+pub fn cooldown_until() -> Option<i64> {
+    let until = COOLDOWN_UNTIL.load(Ordering::SeqCst);
+    (until > chrono::Utc::now().timestamp()).then_some(until)
+}
+
+#[cfg(feature = "test-hooks")]
+pub fn clear_cooldown() {
+    COOLDOWN_UNTIL.store(0, Ordering::SeqCst);
+}
+
+fn start_cooldown(secs: i64) {
+    let secs = secs.clamp(0, MAX_COOLDOWN_SECS);
+    let until = chrono::Utc::now().timestamp() + secs;
+    COOLDOWN_UNTIL.store(until, Ordering::SeqCst);
+    if secs > 0 {
+        crate::log(&format!("usage: rate limited, pausing requests for {}s", secs));
+    }
+}
+
+#[cfg(feature = "test-hooks")]
+fn api_base_override() -> Option<String> {
+    std::env::var(API_BASE_ENV_VAR)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.trim_end_matches('/').to_string())
+}
+
+#[cfg(not(feature = "test-hooks"))]
+fn api_base_override() -> Option<String> {
+    None
+}
+
+/// Seconds to wait from a Retry-After value: either a number of seconds or
+/// an HTTP date.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(secs);
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).num_seconds().max(0) as u64)
+}
+
+fn api_url(path: &str) -> String {
+    format!("{}{}", api_base_override().as_deref().unwrap_or(DEFAULT_API_BASE), path)
+}
+
+fn oauth_token_url() -> String {
+    match api_base_override() {
+        Some(base) => format!("{}/v1/oauth/token", base),
+        None => DEFAULT_OAUTH_TOKEN_URL.to_string(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageBucket {
+    utilization: Option<f64>,
+    resets_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Membership {
+    tier: Option<String>,
+    plan_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtraUsage {
+    is_enabled: Option<bool>,
+    monthly_limit: Option<f64>,
+    used_credits: Option<f64>,
+    utilization: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageLimit {
+    pub label: String,
+    pub usage_pct: f64,
+    pub reset_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtraUsageInfo {
+    pub enabled: bool,
+    pub monthly_limit: f64,
+    pub used_credits: f64,
+    pub utilization: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageData {
+    pub limits: Vec<UsageLimit>,
+    pub extra_usage: Option<ExtraUsageInfo>,
+    pub plan: Option<String>,
+}
+
+/// The last successful usage response and when it was fetched, persisted so a
+/// network outage (or a restart during one) still has numbers to show.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedUsage {
+    /// Unix seconds.
+    pub fetched_at: i64,
+    pub data: UsageData,
+}
+
+pub fn load_cached(path: &Path) -> Option<CachedUsage> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents)
+        .map_err(|e| crate::log(&format!("usage cache: parse error: {}", e)))
+        .ok()
+}
+
+pub fn save_cached(path: &Path, cached: &CachedUsage) {
+    match serde_json::to_string(cached) {
+        Ok(json) => {
+            if let Err(e) = crate::storage::write_tracked("usage_cache", path, &json) {
+                crate::log(&format!("usage cache: write error: {}", e));
+            }
+        }
+        Err(e) => crate::log(&format!("usage cache: serialize error: {}", e)),
+    }
+}
+
+/// Fetch current usage. While a rate-limit cooldown is running this returns
+/// [`UsageError::RateLimited`] without touching the network.
+pub async fn fetch_usage(token: &str) -> Result<UsageData, UsageError> {
+    if let Some(until) = cooldown_until() {
+        crate::log(&format!("usage: cooling down until {}, skipping request", until));
+        return Err(UsageError::RateLimited);
+    }
+    let client = crate::http::client();
+
+    let mut delay = 2;
+    let mut resp = None;
+    for attempt in 0..3 {
+        let r = match client
+            .get(api_url("/api/oauth/usage"))
+            .bearer_auth(token)
+            .header("anthropic-beta", "oauth-2025-04-20")
+            .send()
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                // Transient network failure (DNS, reset, timeout): back off and retry
+                if attempt < 2 {
+                    crate::log(&format!("usage request error, retry {}/2 in {}s: {}", attempt + 1, delay, e));
+                    tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+                    delay *= 2;
+                    continue;
+                }
+                return Err(UsageError::RequestError(e.to_string()));
+            }
+        };
+
+        if r.status() == 429 {
+            // Parse Retry-After header if present, otherwise use exponential backoff
+            let retry_after = r.headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| parse_retry_after(v, Utc::now()));
+            let wait = retry_after.unwrap_or(delay);
+            if attempt < 2 && wait <= MAX_INLINE_WAIT_SECS {
+                crate::log(&format!("usage 429, retry {}/2 in {}s", attempt + 1, wait));
+                tokio::time::sleep(std::time::Duration::from_secs(wait)).await;
+                delay *= 2;
+                continue;
+            }
+            start_cooldown(retry_after.map_or(DEFAULT_COOLDOWN_SECS, |s| s as i64));
+            return Err(UsageError::RateLimited);
+        }
+
+        if r.status().is_server_error() && attempt < 2 {
+            crate::log(&format!("usage HTTP {}, retry {}/2 in {}s", r.status(), attempt + 1, delay));
+            tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+            delay *= 2;
+            continue;
+        }
+
+        resp = Some(r);
+        break;
+    }
+    let resp = resp.unwrap();
+
+    if resp.status() == 401 || resp.status() == 403 {
+        return Err(UsageError::Unauthorized);
+    }
+
+    if !resp.status().is_success() {
+        return Err(UsageError::RequestError(format!("HTTP {}", resp.status())));
+    }
+
+    let raw: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| UsageError::ParseError(e.to_string()))?;
+
+    parse_usage(raw)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Lifetime of the new access token in seconds.
+    pub expires_in: Option<i64>,
+}
+
+/// Exchange a refresh token for a new access token.
+pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse, UsageError> {
+    let resp = crate::http::client()
+        .post(oauth_token_url())
+        .json(&serde_json::json!({
+            "grant_type": "refresh_token",
+            "refresh_token": refresh_token,
+            "client_id": OAUTH_CLIENT_ID,
+        }))
+        .send()
+        .await
+        .map_err(|e| UsageError::RequestError(e.to_string()))?;
+
+    // A rejected refresh token means the user has to sign in again
+    if resp.status() == 400 || resp.status() == 401 || resp.status() == 403 {
+        return Err(UsageError::Unauthorized);
+    }
+    if resp.status() == 429 {
+        return Err(UsageError::RateLimited);
+    }
+    if !resp.status().is_success() {
+        return Err(UsageError::RequestError(format!("HTTP {}", resp.status())));
+    }
+
+    resp.json()
+        .await
+        .map_err(|e| UsageError::ParseError(e.to_string()))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountInfo {
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub organization: Option<String>,
+    pub plan_name: Option<String>,
+}
+
+/// Human-readable plan from the credential blob's `subscriptionType` and
+/// `rateLimitTier`, e.g. ("max", "default_claude_max_20x") -> "Max 20x".
+pub fn plan_display_name(subscription_type: Option<&str>, rate_limit_tier: Option<&str>) -> Option<String> {
+    let tier = rate_limit_tier.unwrap_or("").to_lowercase();
+    let multiplier = ["20x", "5x"].into_iter().find(|m| tier.ends_with(m));
+    let sub = subscription_type.map(str::to_lowercase);
+    let base = match sub.as_deref() {
+        Some("max") => "Max",
+        Some("pro") => "Pro",
+        Some("team") => "Team",
+        Some("enterprise") => "Enterprise",
+        Some(other) if !other.is_empty() => return Some(other.to_string()),
+        _ if tier.contains("max") => "Max",
+        _ => return None,
+    };
+    Some(match multiplier {
+        Some(m) if base == "Max" => format!("Max {}", m),
+        _ => base.to_string(),
+    })
+}
+
+/// Fetch the signed-in account's profile (email, name, organization).
+pub async fn fetch_profile(token: &str) -> Result<AccountInfo, UsageError> {
+    let resp = crate::http::client()
+        .get(api_url("/api/oauth/profile"))
+        .bearer_auth(token)
+        .header("anthropic-beta", "oauth-2025-04-20")
+        .send()
+        .await
+        .map_err(|e| UsageError::RequestError(e.to_string()))?;
+
+    if resp.status() == 401 || resp.status() == 403 {
+        return Err(UsageError::Unauthorized);
+    }
+    if resp.status() == 429 {
+        return Err(UsageError::RateLimited);
+    }
+    if !resp.status().is_success() {
+        return Err(UsageError::RequestError(format!("HTTP {}", resp.status())));
+    }
+
+    let raw: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| UsageError::ParseError(e.to_string()))?;
+
+    Ok(parse_profile(&raw))
+}
+
+fn parse_profile(raw: &serde_json::Value) -> AccountInfo {
+    let text = |obj: &str, key: &str| {
+        raw.get(obj)
+            .and_then(|o| o.get(key))
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+    };
+    AccountInfo {
+        email: text("account", "email").or_else(|| text("account", "email_address")),
+        display_name: text("account", "display_name").or_else(|| text("account", "full_name")),
+        organization: text("organization", "name"),
+        plan_name: plan_display_name(None, text("organization", "rate_limit_tier").as_deref()),
+    }
+}
+
+/// Work out which known layout a raw usage response uses.
+pub fn detect_schema(raw: &serde_json::Value) -> Option<SchemaVersion> {
+    let obj = raw.as_object()?;
+    if V1_BUCKET_KEYS.iter().any(|k| obj.contains_key(*k)) {
+        return Some(SchemaVersion::V1);
+    }
+    None
+}
+
+/// Convert a raw usage response into `UsageData`, whatever its layout.
+///
+/// Unknown fields are ignored and a malformed or missing bucket only drops that
+/// bucket, but a body with none of the known bucket keys is an error rather
+/// than an empty result, so schema drift shows up in the panel and the log.
+pub fn parse_usage(raw: serde_json::Value) -> Result<UsageData, UsageError> {
+    // Log raw API keys for debugging plan detection
+    let keys: Vec<String> = raw
+        .as_object()
+        .map(|obj| obj.keys().cloned().collect())
+        .unwrap_or_default();
+    crate::log(&format!("usage API keys: {:?}", keys));
+
+    match detect_schema(&raw) {
+        Some(SchemaVersion::V1) => Ok(parse_v1(&raw)),
+        None => Err(UsageError::ParseError(format!(
+            "unrecognized usage response (keys: {})",
+            keys.join(", ")
+        ))),
+    }
+}
+
+/// Deserialize one field leniently: absent, `null` or wrongly typed all become `None`.
+fn field<T: serde::de::DeserializeOwned>(raw: &serde_json::Value, key: &str) -> Option<T> {
+    raw.get(key)
+        .filter(|v| !v.is_null())
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+}
+
+fn parse_v1(raw: &serde_json::Value) -> UsageData {
+    let mut limits = Vec::new();
+
+    fn push_bucket(limits: &mut Vec<UsageLimit>, bucket: Option<UsageBucket>, label: &str) {
+        if let Some(b) = bucket {
+            if let Some(util) = b.utilization {
+                limits.push(UsageLimit {
+                    label: label.into(),
+                    usage_pct: util / 100.0,
+                    reset_at: b.resets_at,
+                });
+            }
+        }
+    }
+
+    push_bucket(&mut limits, field(raw, "five_hour"), "Current session");
+    push_bucket(&mut limits, field(raw, "seven_day"), "Current week (all models)");
+    push_bucket(&mut limits, field(raw, "seven_day_sonnet"), "Current week (Sonnet only)");
+    push_bucket(&mut limits, field(raw, "seven_day_opus"), "Current week (Opus only)");
+
+    let extra_usage = field::<ExtraUsage>(raw, "extra_usage").and_then(|eu| {
+        if eu.is_enabled.unwrap_or(false) {
+            Some(ExtraUsageInfo {
+                enabled: true,
+                monthly_limit: eu.monthly_limit.unwrap_or(0.0) / 100.0,
+                used_credits: eu.used_credits.unwrap_or(0.0) / 100.0,
+                utilization: eu.utilization.unwrap_or(0.0) / 100.0,
+            })
+        } else {
+            None
+        }
+    });
+
+    // Try to detect plan from response
+    let membership: Option<Membership> = field(raw, "membership");
+    let plan = field::<String>(raw, "plan")
+        .or_else(|| field(raw, "tier"))
+        .or(membership.as_ref().and_then(|m| m.plan_name.clone()))
+        .or(membership.as_ref().and_then(|m| m.tier.clone()));
+
+    UsageData { limits, extra_usage, plan }
+}
+
+/// Label of the organization's token use today when usage comes from the Admin API.
+pub const ADMIN_TODAY_LABEL: &str = "Today (organization tokens)";
+/// Label of the organization's token use over the last 7 days, kept apart
+/// from the subscription's weekly limit.
+pub const ADMIN_WEEK_LABEL: &str = "Last 7 days (organization tokens)";
+/// Report pages followed at most, as a guard against a `next_page` loop.
+const MAX_REPORT_PAGES: usize = 10;
+
+/// Where usage and costs come from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Backend {
+    /// Claude Code's OAuth usage endpoint, with the plan's rate limits, and
+    /// ccusage for costs.
+    #[default]
+    ClaudeCode,
+    /// The organization usage and cost reports of the Admin API, for Console
+    /// API keys. It has no rate limits to report, so limits are measured
+    /// against `budgets`.
+    AdminApi {
+        #[serde(default)]
+        budgets: AdminBudgets,
+    },
+}
+
+/// A named backend; the profile name also keys its stored Admin API key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageSource {
+    pub profile: String,
+    pub backend: Backend,
+}
+
+impl Default for UsageSource {
+    fn default() -> Self {
+        Self {
+            profile: "default".to_string(),
+            backend: Backend::ClaudeCode,
+        }
+    }
+}
+
+/// Organization budgets that Admin API usage is shown against. Unset budgets
+/// leave out their limit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdminBudgets {
+    /// Tokens per UTC day, across all models.
+    pub daily_tokens: Option<u64>,
+    /// Tokens over the last seven days.
+    pub weekly_tokens: Option<u64>,
+    /// US dollars per calendar month (UTC), shown as the extra-usage budget.
+    pub monthly_spend: Option<f64>,
+}
+
+/// Fetch every page of an Admin API report, from `days` UTC days ago
+/// (including today) in daily buckets, returning the buckets.
+async fn admin_report(api_key: &str, path: &str, days: i64, now: DateTime<Utc>) -> Result<Vec<serde_json::Value>, UsageError> {
+    let start = (now.date_naive() - chrono::Days::new(days.max(1) as u64 - 1)).and_time(NaiveTime::MIN).and_utc();
+    let mut buckets = Vec::new();
+    let mut page: Option<String> = None;
+    for _ in 0..MAX_REPORT_PAGES {
+        let mut query = vec![
+            ("starting_at", start.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            ("bucket_width", "1d".to_string()),
+            ("limit", "31".to_string()),
+        ];
+        if let Some(ref p) = page {
+            query.push(("page", p.clone()));
+        }
+        let resp = crate::http::client()
+            .get(api_url(path))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01")
+            .query(&query)
+            .send()
+            .await
+            .map_err(|e| UsageError::RequestError(e.to_string()))?;
+
+        if resp.status() == 401 || resp.status() == 403 {
+            return Err(UsageError::Credentials("Admin API key was rejected".to_string()));
+        }
+        if resp.status() == 429 {
+            return Err(UsageError::RateLimited);
+        }
+        if !resp.status().is_success() {
+            return Err(UsageError::RequestError(format!("HTTP {}", resp.status())));
+        }
+        let raw: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| UsageError::ParseError(e.to_string()))?;
+
+        let data = raw
+            .get("data")
+            .and_then(|d| d.as_array())
+            .ok_or_else(|| UsageError::ParseError(format!("{}: missing data", path)))?;
+        buckets.extend(data.iter().cloned());
+        page = field::<String>(&raw, "next_page").filter(|_| field(&raw, "has_more").unwrap_or(false));
+        if page.is_none() {
+            return Ok(buckets);
+        }
+    }
+    crate::log(&format!("admin api: {} has more than {} pages, using the first", path, MAX_REPORT_PAGES));
+    Ok(buckets)
+}
+
+fn bucket_start(bucket: &serde_json::Value) -> Option<DateTime<Utc>> {
+    let start = field::<String>(bucket, "starting_at")?;
+    DateTime::parse_from_rfc3339(&start).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Tokens per bucket of a Messages usage report: input (uncached, cache
+/// writes and reads) plus output.
+pub fn report_tokens(buckets: &[serde_json::Value]) -> Vec<(DateTime<Utc>, u64)> {
+    buckets
+        .iter()
+        .filter_map(|bucket| {
+            let results = bucket.get("results")?.as_array()?;
+            let tokens = results
+                .iter()
+                .map(|r| {
+                    let n = |key: &str| field::<u64>(r, key).unwrap_or(0);
+                    let cache_creation = r.get("cache_creation").map_or(0, |c| {
+                        field::<u64>(c, "ephemeral_5m_input_tokens").unwrap_or(0)
+                            + field::<u64>(c, "ephemeral_1h_input_tokens").unwrap_or(0)
+                    });
+                    n("uncached_input_tokens") + cache_creation + n("cache_read_input_tokens") + n("output_tokens")
+                })
+                .sum();
+            Some((bucket_start(bucket)?, tokens))
+        })
+        .collect()
+}
+
+/// US dollars per bucket of a cost report, whose amounts are decimal strings
+/// in cents. Other currencies are skipped.
+pub fn report_costs(buckets: &[serde_json::Value]) -> Vec<(DateTime<Utc>, f64)> {
+    buckets
+        .iter()
+        .filter_map(|bucket| {
+            let results = bucket.get("results")?.as_array()?;
+            let cents: f64 = results
+                .iter()
+                .filter(|r| field::<String>(r, "currency").is_none_or(|c| c == "USD"))
+                .filter_map(|r| match r.get("amount")? {
+                    serde_json::Value::String(s) => s.parse::<f64>().ok(),
+                    v => v.as_f64(),
+                })
+                .sum();
+            Some((bucket_start(bucket)?, cents / 100.0))
+        })
+        .collect()
+}
+
+fn sum_since<T: Copy + std::iter::Sum>(buckets: &[(DateTime<Utc>, T)], since: DateTime<Utc>) -> T {
+    buckets.iter().filter(|(start, _)| *start >= since).map(|(_, v)| *v).sum()
+}
+
+fn days_ago(now: DateTime<Utc>, days: u64) -> DateTime<Utc> {
+    (now.date_naive() - chrono::Days::new(days)).and_time(NaiveTime::MIN).and_utc()
+}
+
+/// Admin API token use (and this month's spend) as `UsageData`, with each
+/// configured budget as a limit.
+pub fn admin_usage_data(
+    tokens: &[(DateTime<Utc>, u64)],
+    month_spend: Option<f64>,
+    budgets: &AdminBudgets,
+    now: DateTime<Utc>,
+) -> UsageData {
+    let mut limits = Vec::new();
+    if let Some(budget) = budgets.daily_tokens.filter(|b| *b > 0) {
+        limits.push(UsageLimit {
+            label: ADMIN_TODAY_LABEL.into(),
+            usage_pct: sum_since(tokens, days_ago(now, 0)) as f64 / budget as f64,
+            reset_at: Some(days_ago(now, 0).checked_add_days(chrono::Days::new(1)).unwrap_or(now).to_rfc3339()),
+        });
+    }
+    if let Some(budget) = budgets.weekly_tokens.filter(|b| *b > 0) {
+        limits.push(UsageLimit {
+            label: ADMIN_WEEK_LABEL.into(),
+            usage_pct: sum_since(tokens, days_ago(now, 6)) as f64 / budget as f64,
+            reset_at: None,
+        });
+    }
+    let extra_usage = budgets.monthly_spend.filter(|b| *b > 0.0).map(|budget| {
+        let spent = month_spend.unwrap_or(0.0);
+        ExtraUsageInfo {
+            enabled: true,
+            monthly_limit: budget,
+            used_credits: spent,
+            utilization: spent / budget,
+        }
+    });
+    UsageData { limits, extra_usage, plan: Some("Console API".to_string()) }
+}
+
+/// Spend today, over the last 7 days and over the last 30, from cost report buckets.
+pub fn admin_cost_data(costs: &[(DateTime<Utc>, f64)], now: DateTime<Utc>) -> CostData {
+    CostData {
+        today: sum_since(costs, days_ago(now, 0)),
+        week: sum_since(costs, days_ago(now, 6)),
+        month: sum_since(costs, days_ago(now, 29)),
+    }
+}
+
+/// Fetch the organization's usage from the Admin API with `api_key`.
+pub async fn fetch_admin_usage(api_key: &str, budgets: &AdminBudgets) -> Result<UsageData, UsageError> {
+    let now = Utc::now();
+    let tokens = report_tokens(&admin_report(api_key, "/v1/organizations/usage_report/messages", 7, now).await?);
+    let month_spend = match budgets.monthly_spend {
+        Some(_) => {
+            let month_start = now.date_naive().with_day(1).unwrap_or(now.date_naive()).and_time(NaiveTime::MIN).and_utc();
+            let days = (now.date_naive() - month_start.date_naive()).num_days() + 1;
+            let costs = report_costs(&admin_report(api_key, "/v1/organizations/cost_report", days, now).await?);
+            Some(sum_since(&costs, month_start))
+        }
+        None => None,
+    };
+    Ok(admin_usage_data(&tokens, month_spend, budgets, now))
+}
+
+/// Fetch the organization's spend from the Admin API cost report.
+pub async fn fetch_admin_costs(api_key: &str) -> Result<CostData, UsageError> {
+    let now = Utc::now();
+    let costs = report_costs(&admin_report(api_key, "/v1/organizations/cost_report", 30, now).await?);
+    Ok(admin_cost_data(&costs, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(json: &str) -> serde_json::Value {
+        serde_json::from_str(json).expect("fixture is valid JSON")
+    }
+
+    fn pct(data: &UsageData, label: &str) -> Option<f64> {
+        data.limits.iter().find(|l| l.label == label).map(|l| l.usage_pct)
+    }
+
+    #[test]
+    fn retry_after_is_seconds_or_an_http_date() {
+        let now = DateTime::parse_from_rfc3339("2026-03-02T10:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(parse_retry_after("120", now), Some(120));
+        assert_eq!(parse_retry_after("Mon, 02 Mar 2026 10:05:00 GMT", now), Some(300));
+        assert_eq!(parse_retry_after("Mon, 02 Mar 2026 09:00:00 GMT", now), Some(0));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn cooldowns_are_capped_at_an_hour() {
+        start_cooldown(7 * 24 * 3600);
+        let left = cooldown_until().unwrap() - Utc::now().timestamp();
+        assert!((MAX_COOLDOWN_SECS - 5..=MAX_COOLDOWN_SECS).contains(&left));
+        COOLDOWN_UNTIL.store(0, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn pro_plan_has_session_and_weekly_only() {
+        let raw = fixture(include_str!("../tests/fixtures/usage/pro.json"));
+        assert_eq!(detect_schema(&raw), Some(SchemaVersion::V1));
+        let data = parse_usage(raw).unwrap();
+        assert_eq!(data.limits.len(), 2);
+        assert_eq!(pct(&data, "Current session"), Some(0.42));
+        assert_eq!(pct(&data, "Current week (all models)"), Some(0.185));
+        assert!(data.extra_usage.is_none());
+        assert!(data.plan.is_none());
+    }
+
+    #[test]
+    fn max_plan_has_model_buckets_extra_usage_and_plan() {
+        let data = parse_usage(fixture(include_str!("../tests/fixtures/usage/max.json"))).unwrap();
+        let labels: Vec<&str> = data.limits.iter().map(|l| l.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            [
+                "Current session",
+                "Current week (all models)",
+                "Current week (Sonnet only)",
+                "Current week (Opus only)",
+            ]
+        );
+        let extra = data.extra_usage.unwrap();
+        assert_eq!(extra.monthly_limit, 50.0);
+        assert_eq!(extra.used_credits, 12.4);
+        assert_eq!(data.plan.as_deref(), Some("Max 20x"));
+    }
+
+    #[test]
+    fn missing_buckets_are_skipped() {
+        let data = parse_usage(fixture(include_str!("../tests/fixtures/usage/missing_buckets.json"))).unwrap();
+        assert_eq!(data.limits.len(), 1);
+        assert_eq!(pct(&data, "Current session"), Some(0.55));
+        assert!(data.limits[0].reset_at.is_none());
+    }
+
+    #[test]
+    fn extra_and_malformed_fields_do_not_break_parsing() {
+        let data = parse_usage(fixture(include_str!("../tests/fixtures/usage/extra_fields.json"))).unwrap();
+        assert_eq!(pct(&data, "Current session"), Some(0.12));
+        assert_eq!(pct(&data, "Current week (all models)"), None);
+        assert_eq!(pct(&data, "Current week (Opus only)"), Some(0.04));
+        assert!(data.extra_usage.is_none());
+        assert_eq!(data.plan.as_deref(), Some("pro"));
+    }
+
+    #[test]
+    fn unknown_schema_is_an_error() {
+        let raw = fixture(include_str!("../tests/fixtures/usage/unknown_schema.json"));
+        assert_eq!(detect_schema(&raw), None);
+        match parse_usage(raw) {
+            Err(UsageError::ParseError(msg)) => assert!(msg.contains("usage")),
+            other => panic!("expected ParseError, got {:?}", other.map(|d| d.limits.len())),
+        }
+    }
+
+    #[test]
+    fn plan_names_from_credential_blob() {
+        assert_eq!(plan_display_name(Some("max"), Some("default_claude_max_20x")).as_deref(), Some("Max 20x"));
+        assert_eq!(plan_display_name(Some("max"), Some("default_claude_max_5x")).as_deref(), Some("Max 5x"));
+        assert_eq!(plan_display_name(Some("pro"), None).as_deref(), Some("Pro"));
+        assert_eq!(plan_display_name(None, Some("default_claude_max_5x")).as_deref(), Some("Max 5x"));
+        assert_eq!(plan_display_name(None, None), None);
+    }
+
+    #[test]
+    fn admin_reports_map_to_budget_limits_and_costs() {
+        let now = DateTime::parse_from_rfc3339("2026-03-10T15:00:00Z").unwrap().with_timezone(&Utc);
+        let usage = fixture(include_str!("../tests/fixtures/admin/usage_report.json"));
+        let tokens = report_tokens(usage["data"].as_array().unwrap());
+        // 1800 + 200 today across two models, 4000 earlier in the week
+        assert_eq!(tokens.iter().map(|(_, t)| t).sum::<u64>(), 6000);
+
+        let budgets = AdminBudgets { daily_tokens: Some(4000), weekly_tokens: Some(12000), monthly_spend: Some(100.0) };
+        let data = admin_usage_data(&tokens, Some(25.0), &budgets, now);
+        assert_eq!(pct(&data, ADMIN_TODAY_LABEL), Some(0.5));
+        assert_eq!(pct(&data, ADMIN_WEEK_LABEL), Some(0.5));
+        assert_eq!(data.limits[0].reset_at.as_deref(), Some("2026-03-11T00:00:00+00:00"));
+        assert_eq!(data.extra_usage.unwrap().utilization, 0.25);
+
+        let costs = fixture(include_str!("../tests/fixtures/admin/cost_report.json"));
+        let costs = admin_cost_data(&report_costs(costs["data"].as_array().unwrap()), now);
+        assert_eq!(costs.today, 12.5);
+        assert_eq!(costs.week, 20.0);
+        assert_eq!(costs.month, 30.0);
+    }
+}
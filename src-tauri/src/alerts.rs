@@ -0,0 +1,338 @@
+use crate::{log, log_warn};
+use crate::settings;
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+
+/// Keep the history file bounded; oldest records are dropped first.
+const MAX_RECORDS: usize = 200;
+/// Attempts per remote channel within a single dispatch (2s, 4s backoff between them).
+const REMOTE_ATTEMPTS: u32 = 3;
+
+/// Serializes read-modify-write cycles on the notification history file.
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Desktop,
+    Slack,
+    Webhook,
+}
+
+impl Channel {
+    fn is_remote(self) -> bool {
+        !matches!(self, Channel::Desktop)
+    }
+}
+
+/// One logical alert. The `id` is derived from what the alert is about (e.g. the
+/// session window it refers to), so detecting the same condition again maps to
+/// the same history record and the same ID on every channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: String,
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelDelivery {
+    pub channel: Channel,
+    pub delivered: bool,
+    pub attempts: u32,
+    pub error: Option<String>,
+    pub last_attempt_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationRecord {
+    pub id: String,
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    pub created_at: i64,
+    pub deliveries: Vec<ChannelDelivery>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct NotificationHistory {
+    pub records: Vec<NotificationRecord>,
+}
+
+fn enabled_channels(app: &tauri::AppHandle) -> Vec<Channel> {
+    let n = settings::get(app).notifications;
+    let mut channels = Vec::new();
+    if n.desktop_enabled {
+        channels.push(Channel::Desktop);
+    }
+    if n.slack_webhook_url.is_some() {
+        channels.push(Channel::Slack);
+    }
+    if n.webhook_url.is_some() {
+        channels.push(Channel::Webhook);
+    }
+    channels
+}
+
+pub fn load_history(app: &tauri::AppHandle) -> NotificationHistory {
+    let path = match storage::app_data_file(app, "notification_history.json") {
+        Some(p) => p,
+        None => return NotificationHistory::default(),
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log_warn(&format!("alerts: history parse error: {}", e));
+            NotificationHistory::default()
+        }),
+        Err(_) => NotificationHistory::default(),
+    }
+}
+
+fn save_history(app: &tauri::AppHandle, history: &NotificationHistory) {
+    let path = match storage::app_data_file(app, "notification_history.json") {
+        Some(p) => p,
+        None => return,
+    };
+    match serde_json::to_string(history) {
+        Ok(json) => {
+            if let Err(e) = storage::write_tracked("notification_history", &path, &json) {
+                log_warn(&format!("alerts: history write error: {}", e));
+            }
+        }
+        Err(e) => log_warn(&format!("alerts: history serialize error: {}", e)),
+    }
+}
+
+/// Deliver an alert to every enabled channel it hasn't already reached.
+///
+/// Calling this again with the same alert ID is cheap and safe: channels that
+/// already succeeded are skipped, and only failed ones are retried. Nothing is
+/// sent while notifications are snoozed or in quiet hours.
+pub async fn dispatch(app: &tauri::AppHandle, alert: Alert) {
+    send(app, alert, enabled_channels(app)).await
+}
+
+/// [`dispatch`] to the desktop only, for notices about this machine rather
+/// than the team's usage, such as app updates.
+pub async fn dispatch_desktop(app: &tauri::AppHandle, alert: Alert) {
+    let channels = enabled_channels(app).into_iter().filter(|ch| !ch.is_remote()).collect();
+    send(app, alert, channels).await
+}
+
+async fn send(app: &tauri::AppHandle, alert: Alert, channels: Vec<Channel>) {
+    // Not recorded, so a condition that still holds fires once the mute ends
+    if let Some(reason) = settings::get(app).muted_reason() {
+        log(&format!("alerts: holding back {} ({})", alert.id, reason));
+        return;
+    }
+    let pending = {
+        let _guard = HISTORY_LOCK.lock().unwrap();
+        pending_channels(&load_history(app), &alert.id, channels)
+    };
+
+    if pending.is_empty() {
+        return;
+    }
+
+    log(&format!("alerts: dispatching {} to {:?}", alert.id, pending));
+
+    let mut results = Vec::new();
+    for channel in pending {
+        results.push(deliver(app, channel, &alert).await);
+    }
+
+    let _guard = HISTORY_LOCK.lock().unwrap();
+    let mut history = load_history(app);
+    record_results(&mut history, &alert, results, chrono::Utc::now().timestamp());
+    save_history(app, &history);
+}
+
+/// The `enabled` channels that haven't delivered alert `id` yet.
+fn pending_channels(history: &NotificationHistory, id: &str, enabled: Vec<Channel>) -> Vec<Channel> {
+    let existing = history.records.iter().find(|r| r.id == id);
+    enabled
+        .into_iter()
+        .filter(|ch| !existing.is_some_and(|r| r.deliveries.iter().any(|d| d.channel == *ch && d.delivered)))
+        .collect()
+}
+
+/// Merge delivery `results` into the record for `alert`, adding it if new,
+/// and drop the oldest records past the limit.
+fn record_results(history: &mut NotificationHistory, alert: &Alert, results: Vec<ChannelDelivery>, now: i64) {
+    let idx = match history.records.iter().position(|r| r.id == alert.id) {
+        Some(i) => i,
+        None => {
+            history.records.push(NotificationRecord {
+                id: alert.id.clone(),
+                kind: alert.kind.clone(),
+                title: alert.title.clone(),
+                body: alert.body.clone(),
+                created_at: now,
+                deliveries: Vec::new(),
+            });
+            history.records.len() - 1
+        }
+    };
+    let record = &mut history.records[idx];
+    for result in results {
+        match record.deliveries.iter_mut().find(|d| d.channel == result.channel) {
+            Some(d) => {
+                d.delivered = result.delivered;
+                d.attempts += result.attempts;
+                d.error = result.error;
+                d.last_attempt_at = result.last_attempt_at;
+            }
+            None => record.deliveries.push(result),
+        }
+    }
+
+    if history.records.len() > MAX_RECORDS {
+        let excess = history.records.len() - MAX_RECORDS;
+        history.records.drain(..excess);
+    }
+}
+
+async fn deliver(app: &tauri::AppHandle, channel: Channel, alert: &Alert) -> ChannelDelivery {
+    let max_attempts = if channel.is_remote() { REMOTE_ATTEMPTS } else { 1 };
+    let mut delay = 2;
+    let mut attempts = 0;
+    let mut error = None;
+
+    while attempts < max_attempts {
+        attempts += 1;
+        let result = match channel {
+            Channel::Desktop => send_desktop(alert),
+            Channel::Slack => send_slack(app, alert).await,
+            Channel::Webhook => send_webhook(app, alert).await,
+        };
+        match result {
+            Ok(()) => {
+                error = None;
+                break;
+            }
+            Err(e) => {
+                log_warn(&format!("alerts: {:?} attempt {}/{} failed: {}", channel, attempts, max_attempts, e));
+                error = Some(e);
+                if attempts < max_attempts {
+                    tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+
+    ChannelDelivery {
+        channel,
+        delivered: error.is_none(),
+        attempts,
+        error,
+        last_attempt_at: chrono::Utc::now().timestamp(),
+    }
+}
+
+fn send_desktop(alert: &Alert) -> Result<(), String> {
+    notify_rust::Notification::new()
+        .summary(&alert.title)
+        .body(&alert.body)
+        .appname("Claudit")
+        .show()
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn send_slack(app: &tauri::AppHandle, alert: &Alert) -> Result<(), String> {
+    let url = settings::get(app)
+        .notifications
+        .slack_webhook_url
+        .ok_or_else(|| "Slack webhook not configured".to_string())?;
+    let payload = serde_json::json!({
+        "text": format!("*{}*\n{}", alert.title, alert.body),
+    });
+    post_json(&url, &payload).await
+}
+
+async fn send_webhook(app: &tauri::AppHandle, alert: &Alert) -> Result<(), String> {
+    let url = settings::get(app)
+        .notifications
+        .webhook_url
+        .ok_or_else(|| "Webhook not configured".to_string())?;
+    let payload = serde_json::json!({
+        "source": "claudit",
+        "id": alert.id,
+        "kind": alert.kind,
+        "title": alert.title,
+        "body": alert.body,
+        "sent_at": chrono::Utc::now().to_rfc3339(),
+    });
+    post_json(&url, &payload).await
+}
+
+async fn post_json(url: &str, payload: &serde_json::Value) -> Result<(), String> {
+    let resp = claudit_core::http::client()
+        .post(url)
+        .json(payload)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(id: &str) -> Alert {
+        Alert { id: id.to_string(), kind: "unused-tokens".into(), title: "t".into(), body: "b".into() }
+    }
+
+    fn delivery(channel: Channel, delivered: bool) -> ChannelDelivery {
+        ChannelDelivery {
+            channel,
+            delivered,
+            attempts: 1,
+            error: (!delivered).then(|| "HTTP 500".to_string()),
+            last_attempt_at: 100,
+        }
+    }
+
+    #[test]
+    fn only_undelivered_channels_are_retried() {
+        let all = vec![Channel::Desktop, Channel::Slack, Channel::Webhook];
+        let mut history = NotificationHistory::default();
+        assert_eq!(pending_channels(&history, "a", all.clone()), all);
+
+        record_results(
+            &mut history,
+            &alert("a"),
+            vec![delivery(Channel::Desktop, true), delivery(Channel::Slack, false)],
+            100,
+        );
+        assert_eq!(pending_channels(&history, "a", all.clone()), [Channel::Slack, Channel::Webhook]);
+        // Other alerts are unaffected
+        assert_eq!(pending_channels(&history, "b", all.clone()), all);
+
+        record_results(&mut history, &alert("a"), vec![delivery(Channel::Slack, true)], 200);
+        assert_eq!(history.records.len(), 1);
+        let slack = &history.records[0].deliveries[1];
+        assert_eq!((slack.delivered, slack.attempts, slack.error.as_deref()), (true, 2, None));
+        assert_eq!(history.records[0].created_at, 100);
+        assert_eq!(pending_channels(&history, "a", all), [Channel::Webhook]);
+    }
+
+    #[test]
+    fn history_keeps_the_newest_records() {
+        let mut history = NotificationHistory::default();
+        for i in 0..MAX_RECORDS + 5 {
+            record_results(&mut history, &alert(&i.to_string()), vec![delivery(Channel::Desktop, true)], i as i64);
+        }
+        assert_eq!(history.records.len(), MAX_RECORDS);
+        assert_eq!(history.records[0].id, "5");
+    }
+}
@@ -0,0 +1,198 @@
+use crate::commands::{collect_costs, collect_usage_from, CostsResult, LastUsage, UsageResult};
+use crate::format::NumberFormat;
+use crate::settings;
+use crate::tray;
+use claudit_core::ccusage::CostCache;
+use serde::Serialize;
+
+pub const HELP: &str = "\
+Usage: claudit [--print usage|costs|all] [--format json|plain]
+
+  --print WHAT     Fetch once, print to stdout and exit without opening the app
+  --format FORMAT  json (default) or plain, a single line for status bars";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Print {
+    Usage,
+    Costs,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Plain,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Headless {
+    pub print: Print,
+    pub format: Format,
+}
+
+#[derive(Serialize)]
+struct Output {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<UsageResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    costs: Option<CostsResult>,
+}
+
+/// The headless request in `args` (without the program name), or `None` to
+/// start the app. Arguments it doesn't know are left to the OS and plugins.
+pub fn parse(args: &[String]) -> Result<Option<Headless>, String> {
+    let mut print = None;
+    let mut format = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--print" => {
+                print = Some(match args.next().map(String::as_str) {
+                    Some("usage") => Print::Usage,
+                    Some("costs") => Print::Costs,
+                    Some("all") => Print::All,
+                    other => return Err(format!("--print takes usage, costs or all, not {:?}", other.unwrap_or(""))),
+                })
+            }
+            "--format" => {
+                format = Some(match args.next().map(String::as_str) {
+                    Some("json") => Format::Json,
+                    Some("plain") => Format::Plain,
+                    other => return Err(format!("--format takes json or plain, not {:?}", other.unwrap_or(""))),
+                })
+            }
+            _ => {}
+        }
+    }
+    match (print, format) {
+        (Some(print), format) => Ok(Some(Headless { print, format: format.unwrap_or(Format::Json) })),
+        (None, Some(_)) => Err("--format only applies with --print".to_string()),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Release builds on Windows have no console of their own, so write to the
+/// one of the shell that started the app, if there is one.
+#[cfg(windows)]
+pub fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // Fails harmlessly without a parent console or when output is redirected
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+pub fn attach_console() {}
+
+/// Fetch what `request` asks for, print it and return the process exit code:
+/// 1 if nothing could be fetched, even from a cache.
+pub fn run(request: Headless) -> i32 {
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("claudit: {}", e);
+            return 1;
+        }
+    };
+    // The app's settings, for the proxy, the active profile and the number format
+    let prefs = settings::load_headless();
+    let source = prefs.usage_source();
+    let fmt = NumberFormat::new(&prefs.format);
+    let output = runtime.block_on(async {
+        let usage = match request.print {
+            Print::Usage | Print::All => Some(collect_usage_from(&source, &LastUsage::new(), None, None).await),
+            Print::Costs => None,
+        };
+        let costs = match request.print {
+            Print::Costs | Print::All => Some(collect_costs(&source, &CostCache::new(), &fmt).await),
+            Print::Usage => None,
+        };
+        Output { usage, costs }
+    });
+
+    let failed = output.usage.as_ref().is_some_and(|u| u.usage.is_none())
+        || output.costs.as_ref().is_some_and(|c| c.costs.is_none());
+    for error in [
+        output.usage.as_ref().and_then(|u| u.usage_error.as_ref()),
+        output.costs.as_ref().and_then(|c| c.costs_error.as_ref()),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        eprintln!("claudit: {}", error);
+    }
+
+    match request.format {
+        Format::Json => {
+            let json = match request.print {
+                Print::Usage => serde_json::to_string_pretty(&output.usage),
+                Print::Costs => serde_json::to_string_pretty(&output.costs),
+                Print::All => serde_json::to_string_pretty(&output),
+            };
+            match json {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    eprintln!("claudit: {}", e);
+                    return 1;
+                }
+            }
+        }
+        Format::Plain => println!("{}", plain(&output, &fmt)),
+    }
+    i32::from(failed)
+}
+
+/// One line such as "Session: 42% (resets 15:00) | Week: 17% | Today: £1.50".
+fn plain(output: &Output, fmt: &NumberFormat) -> String {
+    let mut parts = Vec::new();
+    if let Some(ref usage) = output.usage {
+        match usage.usage {
+            Some(ref data) => parts.extend(tray::usage_lines(data, fmt)),
+            None => parts.push("Usage: unavailable".to_string()),
+        }
+    }
+    if let Some(ref costs) = output.costs {
+        parts.push(match costs.costs {
+            Some(ref c) => format!("Today: {}", fmt.currency(c.today)),
+            None => "Costs: unavailable".to_string(),
+        });
+    }
+    parts.join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parse_reads_print_and_format() {
+        assert_eq!(parse(&args("")), Ok(None));
+        assert_eq!(parse(&args("--print usage")), Ok(Some(Headless { print: Print::Usage, format: Format::Json })));
+        assert_eq!(
+            parse(&args("--format plain --print all")),
+            Ok(Some(Headless { print: Print::All, format: Format::Plain }))
+        );
+        // Arguments for the OS or plugins are left alone
+        assert_eq!(
+            parse(&args("--autostarted --print costs")),
+            Ok(Some(Headless { print: Print::Costs, format: Format::Json }))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_bad_values() {
+        assert_eq!(parse(&args("--print")), Err("--print takes usage, costs or all, not \"\"".to_string()));
+        assert_eq!(parse(&args("--print limits")), Err("--print takes usage, costs or all, not \"limits\"".to_string()));
+        assert_eq!(parse(&args("--print usage --format xml")), Err("--format takes json or plain, not \"xml\"".to_string()));
+        assert_eq!(parse(&args("--format plain")), Err("--format only applies with --print".to_string()));
+    }
+}
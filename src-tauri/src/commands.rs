@@ -1,29 +1,116 @@
-use crate::ccusage::{self, CostCache, CostData};
-use crate::history::{self, UsageSnapshot};
-use crate::keychain;
-use crate::usage_api::{self, UsageData, UsageError};
-use crate::log;
-use serde::Serialize;
-use std::sync::atomic::Ordering;
-use std::sync::Mutex;
-use tauri::{Emitter, Manager, State};
-use tauri_plugin_updater::UpdaterExt;
-
-static LAST_USAGE: Mutex<Option<UsageData>> = Mutex::new(None);
+use crate::alerts::{self, NotificationRecord};
+use crate::detached;
+use crate::diagnostics;
+use crate::events::{self, Event, EventSchema};
+use crate::format::{self, NumberFormat};
+use crate::health::{self, Health};
+use crate::history;
+use crate::preflight::{self, PreflightReport};
+use crate::settings::{self, Settings};
+use crate::simulate::{self, Scenario, SimulatedAlert};
+use crate::storage;
+use crate::tray;
+use crate::updates;
+use crate::{log, log_debug, log_warn};
+use claudit_core::burn_rate::{self, BurnRate};
+use claudit_core::ccusage::{self, CostCache, CostData};
+use claudit_core::exhaustion::{self, ExhaustionEvent};
+use claudit_core::forecast::{self, Forecast};
+use claudit_core::history::{
+    compact_history_at, last_snapshot_at, load_history_at, project_usage, save_snapshot_at, usage_series, CompactStats,
+    ProjectUsage, Resolution, SeriesPoint, UsageSnapshot,
+};
+use claudit_core::projects::{self, Activity, ActivityTracker};
+use claudit_core::sessions::{self, SessionPage, SessionSort};
+use claudit_core::keychain;
+use claudit_core::token;
+use claudit_core::usage_api::{self, AccountInfo, Backend, CachedUsage, UsageData, UsageError, UsageSource};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tauri::{Manager, State};
 
-#[derive(Debug, Clone, Serialize)]
+static LAST_USAGE: LastUsage = LastUsage::new();
+
+/// The last good usage response and the profile it is for, served while the
+/// API can't be reached.
+#[derive(Default)]
+pub struct LastUsage(Mutex<Option<(String, CachedUsage)>>);
+
+impl LastUsage {
+    pub const fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+}
+
+/// Where each transcript was read up to, across polls.
+static ACTIVITY: OnceLock<Mutex<ActivityTracker>> = OnceLock::new();
+
+/// Numbers [`refresh_all`] runs.
+static REFRESH_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+const EXHAUSTION_FILE: &str = "exhaustion_events.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UsageResult {
     pub usage: Option<UsageData>,
     pub usage_error: Option<String>,
-    pub usage_history: Option<Vec<UsageSnapshot>>,
     pub timestamp: String,
     pub rate_limited: bool,
+    /// `usage` is the last good response rather than a fresh one.
+    pub stale: bool,
+    /// When `usage` was fetched, in Unix seconds.
+    pub last_updated: Option<i64>,
+    /// While rate limited, when requests resume, in Unix seconds. Polls before
+    /// then are served from the cache without contacting the API.
+    pub cooldown_until: Option<i64>,
+    /// Filled in by `get_usage_data`, which knows the display settings.
+    pub display: Option<UsageDisplay>,
+    /// The refresh this belongs to; see [`refresh_all`].
+    pub generation: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CostsResult {
     pub costs: Option<CostData>,
     pub costs_error: Option<String>,
+    pub formatted: Option<FormattedCosts>,
+    /// `costs` come from an earlier ccusage run rather than this one.
+    pub stale: bool,
+    /// When `costs` were computed, in Unix seconds.
+    pub last_updated: Option<i64>,
+    /// A fresh result will follow in another `costs-ready` event.
+    pub refreshing: bool,
+    /// The refresh this belongs to; see [`refresh_all`].
+    pub generation: Option<u64>,
+}
+
+/// Payload of the `history-ready` event, which says the usage history can be
+/// queried with `get_usage_series`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryResult {
+    /// The refresh this belongs to; see [`refresh_all`].
+    pub generation: Option<u64>,
+}
+
+/// Costs as display strings in the user's number format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormattedCosts {
+    pub today: String,
+    pub week: String,
+    pub month: String,
+}
+
+/// Display strings for a [`UsageResult`] in the user's number format.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct UsageDisplay {
+    /// Percentage per limit label.
+    pub limits: HashMap<String, String>,
+    pub extra_usage_spend: Option<String>,
+    /// Local time requests resume after a rate-limit cooldown, e.g. "14:05".
+    pub retry_at: Option<String>,
 }
 
 async fn fetch_with_timeout<T, E: std::fmt::Display>(
@@ -37,95 +124,316 @@ async fn fetch_with_timeout<T, E: std::fmt::Display>(
     ).await;
     match result {
         Ok(Ok(data)) => { log(&format!("{} OK", label)); (Some(data), None) }
-        Ok(Err(e)) => { log(&format!("{} error: {}", label, e)); (None, Some(e.to_string())) }
-        Err(_) => { log(&format!("{} timeout", label)); (None, Some("Request timed out".to_string())) }
+        Ok(Err(e)) => { log_warn(&format!("{} error: {}", label, e)); (None, Some(e.to_string())) }
+        Err(_) => { log_warn(&format!("{} timeout", label)); (None, Some("Request timed out".to_string())) }
     }
 }
 
+/// Tokens used since the last snapshot at `history_path` (or one poll
+/// interval for the first), bounded to a week after long gaps.
+fn activity_since_last_snapshot(history_path: &Path) -> Activity {
+    let dir = match projects::projects_dir() {
+        Some(d) => d,
+        None => return Activity::default(),
+    };
+    let now = chrono::Utc::now().timestamp();
+    let since = last_snapshot_at(history_path)
+        .unwrap_or(now - 300)
+        .max(now - 7 * 24 * 3600);
+    ACTIVITY
+        .get_or_init(|| Mutex::new(ActivityTracker::new()))
+        .lock()
+        .unwrap()
+        .activity_between(&dir, since, now)
+}
+
 #[tauri::command]
 pub async fn get_usage_data(app: tauri::AppHandle) -> Result<UsageResult, ()> {
-    log("get_usage_data: starting");
+    Ok(usage_result(&app).await)
+}
+
+/// Usage for the panel, with display strings, and the tray and auto-show
+/// brought up to date.
+pub(crate) async fn usage_result(app: &tauri::AppHandle) -> UsageResult {
+    log_debug("get_usage_data: starting");
+    let cache_path = storage::profile_data_file(app, "last_usage.json");
+    let source = settings::get(app).usage_source();
+    let history_path = history::get_history_path(app);
+    let mut result = collect_usage_from(&source, &LAST_USAGE, history_path.clone(), cache_path).await;
+
+    if let (Some(history_path), Some(path)) = (history_path, storage::profile_data_file(app, EXHAUSTION_FILE)) {
+        let _ = tokio::task::spawn_blocking(move || {
+            let snapshots = load_history_at(&history_path).snapshots;
+            if let Err(e) = exhaustion::update_archive(&path, &snapshots) {
+                log_warn(&format!("exhaustion: archive write error: {}", e));
+            }
+        })
+        .await;
+    }
+
+    let retry_at = result.cooldown_until.and_then(format::local_time);
+    if let Some(until) = result.cooldown_until {
+        tray::show_cooldown(app, until, result.usage.as_ref());
+    }
+
+    if let Some(ref data) = result.usage {
+        let fmt = format::for_app(app);
+        result.display = Some(UsageDisplay {
+            limits: data
+                .limits
+                .iter()
+                .map(|l| (l.label.clone(), fmt.percent(l.usage_pct.min(1.0))))
+                .collect(),
+            extra_usage_spend: data.extra_usage.as_ref().map(|eu| fmt.currency(eu.used_credits)),
+            retry_at,
+        });
+        if result.cooldown_until.is_none() {
+            tray::update(app, data);
+        }
+        crate::notifier::maybe_auto_show(app, data).await;
+    }
+
+    log_debug("get_usage_data: done");
+    result
+}
+
+/// The last good usage response for the active profile, if any has been
+/// seen this run.
+pub(crate) fn last_usage(app: &tauri::AppHandle) -> Option<UsageData> {
+    let profile = settings::get(app).usage_source().profile;
+    LAST_USAGE.0.lock().unwrap().as_ref().filter(|(p, _)| *p == profile).map(|(_, c)| c.data.clone())
+}
+
+/// Fetch usage, record a history snapshot and return both. When the request
+/// fails, the last good response (kept in `last` and at `cache_path`) is
+/// returned flagged `stale` alongside the error, and no snapshot is taken.
+/// Free of window and tray handling so the whole pipeline can run without an
+/// app, as in the integration tests.
+pub async fn collect_usage(last: &LastUsage, history_path: Option<PathBuf>, cache_path: Option<PathBuf>) -> UsageResult {
+    collect_usage_from(&UsageSource::default(), last, history_path, cache_path).await
+}
+
+/// [`collect_usage`] from `source` rather than Claude Code's usage endpoint.
+pub async fn collect_usage_from(
+    source: &UsageSource,
+    last: &LastUsage,
+    history_path: Option<PathBuf>,
+    cache_path: Option<PathBuf>,
+) -> UsageResult {
     let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
 
-    let token_result = tokio::task::spawn_blocking(keychain::get_oauth_token)
-        .await
-        .map_err(|e| e.to_string())
-        .and_then(|r| r.map_err(|e| e.to_string()));
-    log(&format!("get_usage_data: keychain result={}", token_result.is_ok()));
-
-    let (usage, usage_error, rate_limited) = match token_result {
-        Ok(ref token) => {
-            log("get_usage_data: fetching usage API");
-            match tokio::time::timeout(
-                std::time::Duration::from_secs(30),
-                usage_api::fetch_usage(token),
-            ).await {
-                Ok(Ok(data)) => {
-                    log("usage OK");
-                    let mut cache = LAST_USAGE.lock().unwrap();
-                    *cache = Some(data.clone());
-                    (Some(data), None, false)
-                }
-                Ok(Err(UsageError::RateLimited)) => {
-                    let cache = LAST_USAGE.lock().unwrap();
-                    if cache.is_some() {
-                        log("usage rate-limited, returning cached data");
-                        (cache.clone(), None, true)
-                    } else {
-                        log("usage rate-limited, no cached data");
-                        (None, Some("Rate limited - please wait a moment".to_string()), true)
-                    }
+    log_debug("get_usage_data: fetching usage API");
+    let fetched = match tokio::time::timeout(
+        std::time::Duration::from_secs(30),
+        token::fetch_usage_from(source),
+    ).await {
+        Ok(result) => result,
+        Err(_) => Err(UsageError::RequestError("Request timed out".to_string())),
+    };
+
+    let (usage, usage_error, rate_limited, stale, last_updated) = match fetched {
+        Ok(data) => {
+            log("usage OK");
+            let cached = CachedUsage { fetched_at: chrono::Utc::now().timestamp(), data: data.clone() };
+            if let Some(ref path) = cache_path {
+                usage_api::save_cached(path, &cached);
+            }
+            let fetched_at = cached.fetched_at;
+            *last.0.lock().unwrap() = Some((source.profile.clone(), cached));
+            (Some(data), None, false, false, Some(fetched_at))
+        }
+        Err(e) => {
+            let rate_limited = matches!(e, UsageError::RateLimited);
+            // Rate limiting is expected and transient, so cached data is served silently
+            let retry_at = usage_api::cooldown_until().and_then(format::local_time);
+            let error = if rate_limited {
+                match retry_at {
+                    Some(t) => format!("Rate limited - cooling down until {}", t),
+                    None => "Rate limited - please wait a moment".to_string(),
                 }
-                Ok(Err(e)) => {
-                    log(&format!("usage error: {}", e));
-                    (None, Some(e.to_string()), false)
+            } else {
+                e.to_string()
+            };
+            log_warn(&format!("usage error: {}", error));
+
+            let cached = {
+                let mut last = last.0.lock().unwrap();
+                // Never another profile's data
+                if last.as_ref().is_none_or(|(profile, _)| *profile != source.profile) {
+                    *last = cache_path
+                        .as_deref()
+                        .and_then(usage_api::load_cached)
+                        .map(|c| (source.profile.clone(), c));
                 }
-                Err(_) => {
-                    log("usage timeout");
-                    (None, Some("Request timed out".to_string()), false)
+                last.as_ref().map(|(_, c)| c.clone())
+            };
+            match cached {
+                Some(c) => {
+                    log(&format!("usage: serving last good data from {}", c.fetched_at));
+                    let error = if rate_limited { None } else { Some(error) };
+                    (Some(c.data), error, rate_limited, true, Some(c.fetched_at))
                 }
+                None => (None, Some(error), rate_limited, false, None),
             }
         }
-        Err(ref e) => (None, Some(e.clone()), false),
     };
 
-    if let Some(ref data) = usage {
-        if let Some(session) = data.limits.iter().find(|l| l.label == "Current session") {
-            let pct = (session.usage_pct * 100.0).floor() as i32;
-            let title = format!("{}%", pct);
-            log(&format!("set tray title: {}", title));
-            if let Some(tray) = app.tray_by_id("main-tray") {
-                let _ = tray.set_title(Some(&title));
-            } else {
-                log("tray not found by id main-tray");
-            }
-        }
+    // A stale response was already recorded when it was fresh
+    if let (Some(path), Some(data), false) = (history_path, usage.clone(), stale) {
+        let _ = tokio::task::spawn_blocking(move || {
+            let activity = activity_since_last_snapshot(&path);
+            save_snapshot_at(&path, &data, activity);
+        })
+        .await;
     }
 
-    let usage_history = {
-        let app_clone = app.clone();
-        let usage_for_save = usage.clone();
-        tokio::task::spawn_blocking(move || {
-            if let Some(ref data) = usage_for_save {
-                history::save_snapshot(&app_clone, data);
-            }
-            history::load_history(&app_clone).snapshots
-        })
+    UsageResult {
+        usage,
+        usage_error,
+        timestamp,
+        rate_limited,
+        stale,
+        last_updated,
+        cooldown_until: if rate_limited { usage_api::cooldown_until() } else { None },
+        display: None,
+        generation: None,
+    }
+}
+
+#[tauri::command]
+pub async fn get_account_info() -> Result<AccountInfo, String> {
+    log_debug("get_account_info: starting");
+    let creds = tokio::task::spawn_blocking(keychain::get_credentials)
         .await
-        .ok()
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+    let blob_plan = usage_api::plan_display_name(
+        creds.subscription_type.as_deref(),
+        creds.rate_limit_tier.as_deref(),
+    );
+
+    let access_token = token::access_token_from(creds).await;
+    let (profile, profile_error) = fetch_with_timeout(
+        "profile",
+        15,
+        usage_api::fetch_profile(&access_token),
+    ).await;
+
+    // The credential blob names the plan reliably; the profile fills in who it belongs to
+    let info = match profile {
+        Some(p) => AccountInfo { plan_name: blob_plan.or(p.plan_name), ..p },
+        None if blob_plan.is_some() => AccountInfo {
+            email: None,
+            display_name: None,
+            organization: None,
+            plan_name: blob_plan,
+        },
+        None => return Err(profile_error.unwrap_or_else(|| "Account info unavailable".to_string())),
     };
+    log_debug("get_account_info: done");
+    Ok(info)
+}
 
-    log("get_usage_data: done");
-    Ok(UsageResult { usage, usage_error, usage_history, timestamp, rate_limited })
+#[tauri::command]
+pub async fn get_costs_data(app: tauri::AppHandle, cost_cache: State<'_, CostCache>) -> Result<CostsResult, ()> {
+    Ok(costs_result(&app, cost_cache.inner().clone()).await)
 }
 
+pub(crate) async fn costs_result(app: &tauri::AppHandle, cost_cache: CostCache) -> CostsResult {
+    log_debug("get_costs_data: starting");
+    let prefs = settings::get(app);
+    let result = collect_costs(&prefs.usage_source(), &cost_cache, &NumberFormat::new(&prefs.format)).await;
+    if let Some(ref costs) = result.costs {
+        tray::update_costs(app, costs);
+    }
+    log_debug("get_costs_data: done");
+    result
+}
+
+/// Costs from `source`: ccusage through `cost_cache`, falling back to its last
+/// good result (flagged `stale`) on failure, or the Admin API cost report.
+/// Needs no app, like [`collect_usage`].
+pub(crate) async fn collect_costs(source: &UsageSource, cost_cache: &CostCache, fmt: &NumberFormat) -> CostsResult {
+    if let Backend::AdminApi { .. } = source.backend {
+        // Organization costs are a different total from ccusage's, so they
+        // don't share its cache
+        let (costs, costs_error) = fetch_with_timeout("admin costs", 45, token::fetch_admin_costs(&source.profile)).await;
+        let fetched_at = costs.as_ref().map(|_| chrono::Utc::now().timestamp());
+        return build_costs_result(fmt, costs, costs_error, fetched_at, false);
+    }
+    let (costs, costs_error) = fetch_with_timeout("costs", 45, ccusage::fetch_costs(cost_cache)).await;
+    match (costs, cost_cache.get_any()) {
+        (Some(c), cached) => build_costs_result(fmt, Some(c), None, cached.map(|c| c.fetched_at), false),
+        // Fall back to the last good run, flagged stale, alongside the error
+        (None, Some(cached)) => build_costs_result(fmt, Some(cached.data), costs_error, Some(cached.fetched_at), true),
+        (None, None) => build_costs_result(fmt, None, costs_error, None, false),
+    }
+}
+
+fn build_costs_result(
+    fmt: &NumberFormat,
+    costs: Option<CostData>,
+    costs_error: Option<String>,
+    last_updated: Option<i64>,
+    stale: bool,
+) -> CostsResult {
+    let formatted = costs.as_ref().map(|c| FormattedCosts {
+        today: fmt.currency(c.today),
+        week: fmt.currency(c.week),
+        month: fmt.currency(c.month),
+    });
+    CostsResult { costs, costs_error, formatted, stale, last_updated, refreshing: false, generation: None }
+}
+
+/// Kick off usage, costs and history fetches at once. Each result is emitted
+/// as its own event (`usage-ready`, `costs-ready`, `history-ready`) as soon as
+/// it arrives, so a slow ccusage run doesn't hold back the rest.
 #[tauri::command]
-pub async fn get_costs_data(cost_cache: State<'_, CostCache>) -> Result<CostsResult, ()> {
-    log("get_costs_data: starting");
-    let cost_cache_ref = cost_cache.inner().clone();
-    let (costs, costs_error) = fetch_with_timeout("costs", 45, ccusage::fetch_costs(&cost_cache_ref)).await;
-    log("get_costs_data: done");
-    Ok(CostsResult { costs, costs_error })
+pub fn get_all_data(app: tauri::AppHandle, cost_cache: State<'_, CostCache>) -> Result<(), ()> {
+    refresh_all(app, cost_cache.inner().clone());
+    Ok(())
+}
+
+/// What [`get_all_data`] starts. Each run's results carry a higher
+/// `generation` than the last, so a slow result that lands after a newer one
+/// can be dropped.
+pub(crate) fn refresh_all(app: tauri::AppHandle, cost_cache: CostCache) {
+    let generation = Some(REFRESH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1);
+
+    let history_app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        events::emit(&history_app, Event::HistoryReady(HistoryResult { generation }));
+    });
+
+    let usage_app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut result = usage_result(&usage_app).await;
+        result.generation = generation;
+        events::emit(&usage_app, Event::UsageReady(result));
+    });
+
+    // Show the previous run (possibly from before a restart) while ccusage runs
+    if let Some(cached) = cost_cache.get_any().filter(|c| !c.is_fresh()) {
+        let mut early = build_costs_result(&format::for_app(&app), Some(cached.data), None, Some(cached.fetched_at), true);
+        early.refreshing = true;
+        early.generation = generation;
+        events::emit(&app, Event::CostsReady(early));
+    }
+    tauri::async_runtime::spawn(async move {
+        let mut result = costs_result(&app, cost_cache).await;
+        result.generation = generation;
+        events::emit(&app, Event::CostsReady(result));
+    });
+}
+
+/// Refresh only the costs and emit them, as after new activity; usage stays
+/// on its own schedule. Numbered like [`refresh_all`].
+pub(crate) async fn refresh_costs(app: &tauri::AppHandle, cost_cache: CostCache) {
+    let generation = Some(REFRESH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1);
+    let mut result = costs_result(app, cost_cache).await;
+    result.generation = generation;
+    events::emit(app, Event::CostsReady(result));
 }
 
 #[tauri::command]
@@ -140,49 +448,278 @@ pub async fn hide_panel(app: tauri::AppHandle) -> Result<(), ()> {
 #[tauri::command]
 pub async fn detach_panel(app: tauri::AppHandle) -> Result<(), ()> {
     log("detach_panel: detaching");
-    if let Some(window) = app.get_webview_window("panel") {
-        let stay_on_top = crate::STAY_ON_TOP_DETACHED.load(Ordering::SeqCst);
-        let _ = window.set_always_on_top(stay_on_top);
-        let _ = window.set_resizable(true);
-        let _ = window.set_min_size(Some(tauri::LogicalSize::new(300.0, 400.0)));
-        crate::PANEL_DETACHED.store(true, Ordering::SeqCst);
-        let _ = app.emit("panel-detached", ());
-        log("detach_panel: done");
-    }
+    detached::detach(&app);
+    log("detach_panel: done");
     Ok(())
 }
 
 #[tauri::command]
 pub async fn attach_panel(app: tauri::AppHandle) -> Result<(), ()> {
     log("attach_panel: re-docking");
-    if let Some(window) = app.get_webview_window("panel") {
-        let _ = window.set_always_on_top(true);
-        let _ = window.set_resizable(false);
-        let _ = window.set_min_size(None::<tauri::LogicalSize<f64>>);
-        let _ = window.set_size(tauri::LogicalSize::new(crate::PANEL_WIDTH, crate::PANEL_HEIGHT));
-        crate::PANEL_DETACHED.store(false, Ordering::SeqCst);
-        crate::PANEL_VISIBLE.store(false, Ordering::SeqCst);
-        let _ = window.hide();
-        let _ = app.emit("panel-attached", ());
-        log("attach_panel: done, panel hidden");
-    }
+    detached::attach(&app);
+    log("attach_panel: done, panel hidden");
     Ok(())
 }
 
 #[tauri::command]
-pub fn set_stay_on_top_pref(enabled: bool) -> Result<(), ()> {
+pub fn set_stay_on_top_pref(app: tauri::AppHandle, enabled: bool) -> Result<(), ()> {
     log(&format!("set_stay_on_top_pref: {}", enabled));
     crate::STAY_ON_TOP_DETACHED.store(enabled, Ordering::SeqCst);
+    if settings::get(&app).panel.stay_on_top != enabled {
+        settings::update(&app, |s| s.panel.stay_on_top = enabled);
+    }
     Ok(())
 }
 
 #[tauri::command]
-pub fn set_notify_tokens_pref(enabled: bool) -> Result<(), ()> {
+pub fn set_notify_tokens_pref(app: tauri::AppHandle, enabled: bool) -> Result<(), ()> {
     log(&format!("set_notify_tokens_pref: {}", enabled));
-    crate::notifier::NOTIFICATIONS_ENABLED.store(enabled, Ordering::SeqCst);
+    if settings::get(&app).notifications.enabled != enabled {
+        settings::update(&app, |s| s.notifications.enabled = enabled);
+    }
     Ok(())
 }
 
+/// Snooze notifications for "1h", "4h" or "today", or resume them with no
+/// duration. Returns when the snooze ends.
+#[tauri::command]
+pub fn snooze_notifications(app: tauri::AppHandle, duration: Option<String>) -> Result<Option<i64>, String> {
+    log(&format!("snooze_notifications: {:?}", duration));
+    crate::notifier::snooze(&app, duration.as_deref())
+}
+
+/// Store the Admin API key for `profile` in the keychain, or remove it with no key.
+#[tauri::command]
+pub async fn set_admin_api_key(profile: String, key: Option<String>) -> Result<(), String> {
+    log(&format!("set_admin_api_key: {} ({})", profile, if key.is_some() { "set" } else { "removed" }));
+    tokio::task::spawn_blocking(move || keychain::set_admin_api_key(&profile, key.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Whether an Admin API key is available for `profile`.
+#[tauri::command]
+pub async fn has_admin_api_key(profile: String) -> Result<bool, String> {
+    tokio::task::spawn_blocking(move || keychain::admin_api_key(&profile).is_ok())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Store the proxy password in the keychain, or remove it with no password.
+#[tauri::command]
+pub async fn set_proxy_password(app: tauri::AppHandle, password: Option<String>) -> Result<(), String> {
+    log(&format!("set_proxy_password: {}", if password.is_some() { "set" } else { "removed" }));
+    tokio::task::spawn_blocking(move || settings::set_proxy_password(&app, password.as_deref()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Version and names of the events the backend emits.
+#[tauri::command]
+pub fn get_event_schema() -> EventSchema {
+    events::schema()
+}
+
+#[tauri::command]
+pub fn get_settings(app: tauri::AppHandle) -> Result<Settings, String> {
+    Ok(settings::get(&app))
+}
+
+#[tauri::command]
+pub fn update_settings(app: tauri::AppHandle, settings: Settings) -> Result<Settings, String> {
+    log("update_settings: saving");
+    settings::save(&app, settings)
+}
+
+#[tauri::command]
+pub async fn get_notification_history(app: tauri::AppHandle) -> Result<Vec<NotificationRecord>, String> {
+    tokio::task::spawn_blocking(move || alerts::load_history(&app).records)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// The most recent usage the panel fetched, or a fresh fetch if there is none.
+async fn current_usage(app: &tauri::AppHandle) -> Result<UsageData, String> {
+    let cached = last_usage(app);
+    match cached {
+        Some(u) => Ok(u),
+        None => token::fetch_usage_from(&settings::get(app).usage_source()).await.map_err(|e| e.to_string()),
+    }
+}
+
+/// The active profile's usage history, read off the main thread.
+async fn load_snapshots(app: &tauri::AppHandle) -> Result<Vec<UsageSnapshot>, String> {
+    let app = app.clone();
+    tokio::task::spawn_blocking(move || history::load_history(&app).snapshots)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_burn_rate(app: tauri::AppHandle) -> Result<BurnRate, String> {
+    let usage = current_usage(&app).await?;
+    let session = usage
+        .limits
+        .iter()
+        .find(|l| l.label == burn_rate::SESSION_LABEL)
+        .ok_or_else(|| "No session limit in usage data".to_string())?;
+
+    let snapshots = load_snapshots(&app).await?;
+    let mut rate = burn_rate::compute(&snapshots, session, chrono::Utc::now());
+    // A hung ccusage leaves the rate without tokens rather than blocking it
+    rate.tokens_per_hour = fetch_with_timeout("ccusage blocks", 45, ccusage::fetch_tokens_per_hour()).await.0.flatten();
+    let fmt = format::for_app(&app);
+    rate.tokens_per_hour_display = rate.tokens_per_hour.map(|t| format!("{}/h", fmt.tokens(t)));
+    Ok(rate)
+}
+
+#[tauri::command]
+pub async fn get_forecast(app: tauri::AppHandle) -> Result<Forecast, String> {
+    let usage = current_usage(&app).await?;
+    let snapshots = load_snapshots(&app).await?;
+    Ok(forecast::compute(&snapshots, &usage, chrono::Utc::now()))
+}
+
+/// How much of a limit each project used in its current window; the weekly
+/// all-models limit unless `label` names another.
+#[tauri::command]
+pub async fn get_project_usage(app: tauri::AppHandle, label: Option<String>) -> Result<Vec<ProjectUsage>, String> {
+    let label = label.unwrap_or_else(|| "Current week (all models)".to_string());
+    let usage = current_usage(&app).await?;
+    let limit = usage
+        .limits
+        .iter()
+        .find(|l| l.label == label)
+        .ok_or_else(|| format!("No \"{}\" limit in usage data", label))?;
+    let window = if label == burn_rate::SESSION_LABEL { 5 * 3600 } else { 7 * 24 * 3600 };
+    let since = limit
+        .reset_at
+        .as_deref()
+        .and_then(|r| chrono::DateTime::parse_from_rfc3339(r).ok())
+        .map_or(chrono::Utc::now().timestamp() - window, |r| r.timestamp() - window);
+
+    let snapshots = load_snapshots(&app).await?;
+    Ok(project_usage(&snapshots, &label, since))
+}
+
+/// Limit `label` aggregated per `resolution` interval between `from` and `to`
+/// (Unix seconds; the last week up to now by default), for charts.
+#[tauri::command]
+pub async fn get_usage_series(
+    app: tauri::AppHandle,
+    label: String,
+    from: Option<i64>,
+    to: Option<i64>,
+    resolution: Resolution,
+) -> Result<Vec<SeriesPoint>, String> {
+    let to = to.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let from = from.unwrap_or(to - 7 * 24 * 3600);
+    if from > to {
+        return Err("Series range ends before it starts".to_string());
+    }
+    // Reads and aggregates the whole history, so off the main thread
+    tokio::task::spawn_blocking(move || {
+        let snapshots = history::load_history(&app).snapshots;
+        usage_series(&snapshots, &label, from, to, resolution, local_utc_offset)
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Seconds local time is ahead of UTC at Unix time `t`.
+fn local_utc_offset(t: i64) -> i64 {
+    use chrono::{Offset, TimeZone};
+    chrono::Local
+        .timestamp_opt(t, 0)
+        .single()
+        .map_or(0, |dt| dt.offset().fix().local_minus_utc() as i64)
+}
+
+/// Furthest back `get_sessions` looks; Claude Code deletes older
+/// transcripts after 30 days by default anyway.
+const MAX_SESSION_DAYS: i64 = 365;
+
+/// Claude Code sessions with activity in the last `days` (30 by default, at
+/// most a year), sorted by `sort` (most recent first by default), `limit`
+/// from `offset`.
+#[tauri::command]
+pub async fn get_sessions(
+    offset: Option<usize>,
+    limit: Option<usize>,
+    sort: Option<SessionSort>,
+    days: Option<i64>,
+) -> Result<SessionPage, String> {
+    let dir = projects::projects_dir().ok_or("No home directory")?;
+    let since = chrono::Utc::now().timestamp() - days.unwrap_or(30).clamp(1, MAX_SESSION_DAYS) * 24 * 3600;
+    let all = tokio::task::spawn_blocking(move || sessions::sessions_since(&dir, since))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(sessions::page(all, sort.unwrap_or_default(), offset.unwrap_or(0), limit.unwrap_or(20).min(200)))
+}
+
+/// Apply the retention settings to the stored history now rather than on the
+/// next poll.
+#[tauri::command]
+pub async fn compact_history(app: tauri::AppHandle) -> Result<CompactStats, String> {
+    let path = history::get_history_path(&app).ok_or("No app data directory")?;
+    tokio::task::spawn_blocking(move || compact_history_at(&path))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Every recorded time a limit hit 100%, oldest first.
+#[tauri::command]
+pub async fn get_exhaustion_events(app: tauri::AppHandle) -> Result<Vec<ExhaustionEvent>, String> {
+    let path = storage::profile_data_file(&app, EXHAUSTION_FILE).ok_or("No app data directory")?;
+    tokio::task::spawn_blocking(move || exhaustion::load_events(&path))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Run the pre-flight check now, whether or not it is scheduled.
+#[tauri::command]
+pub async fn run_preflight(app: tauri::AppHandle) -> Result<PreflightReport, String> {
+    Ok(preflight::run(&app, true).await)
+}
+
+#[tauri::command]
+pub fn get_health() -> Result<Health, String> {
+    Ok(health::current())
+}
+
+/// Save a zip for bug reports to `path`, or where the user picks in a save
+/// dialog, returning where it went. `None` if the dialog was cancelled.
+#[tauri::command]
+pub async fn generate_diagnostics(app: tauri::AppHandle, path: Option<String>) -> Result<Option<String>, String> {
+    log(&format!("generate_diagnostics: {:?}", path));
+    // The dialog blocks, and the zip reads and deflates the logs, so both
+    // stay off the main thread
+    tokio::task::spawn_blocking(move || {
+        let Some(path) = path.map(PathBuf::from).or_else(|| diagnostics::choose_path(&app)) else {
+            log("generate_diagnostics: cancelled");
+            return Ok(None);
+        };
+        diagnostics::generate(&app, &path)?;
+        Ok(Some(path.display().to_string()))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn simulate_rules(app: tauri::AppHandle, scenario: Scenario) -> Result<Vec<SimulatedAlert>, String> {
+    let settings = settings::get(&app);
+    if let Some(ref rules) = scenario.rules {
+        Settings { rules: rules.clone(), ..settings.clone() }.validate()?;
+    }
+    // Up to 20k rule evaluations, so off the main thread
+    tokio::task::spawn_blocking(move || simulate::run(&scenario, &settings))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 pub async fn get_autostart_enabled(app: tauri::AppHandle) -> Result<bool, String> {
     use tauri_plugin_autostart::ManagerExt;
@@ -214,22 +751,17 @@ pub async fn check_for_updates(app: tauri::AppHandle) -> Result<UpdateInfo, Stri
     log("check_for_updates: using tauri updater plugin");
     let current = env!("CARGO_PKG_VERSION").to_string();
 
-    let updater = app.updater().map_err(|e| e.to_string())?;
-    let update = updater.check().await.map_err(|e| e.to_string())?;
-
-    match update {
-        Some(update) => {
-            let latest = update.version.clone();
-            let notes = update.body.clone();
+    match updates::check(&app).await? {
+        Some(found) => {
             log(&format!(
                 "check_for_updates: current={}, latest={}, update available",
-                current, latest
+                current, found.version
             ));
             Ok(UpdateInfo {
                 current_version: current,
-                latest_version: latest,
+                latest_version: found.version,
                 update_available: true,
-                release_notes: notes,
+                release_notes: found.notes,
             })
         }
         None => {
@@ -246,22 +778,17 @@ pub async fn check_for_updates(app: tauri::AppHandle) -> Result<UpdateInfo, Stri
 
 #[tauri::command]
 pub async fn install_update(app: tauri::AppHandle) -> Result<(), String> {
-    log("install_update: checking for update");
-    let updater = app.updater().map_err(|e| e.to_string())?;
-    let update = updater.check().await.map_err(|e| e.to_string())?;
-
-    let update = update.ok_or_else(|| "No update available".to_string())?;
-    log(&format!("install_update: downloading v{}", update.version));
-
-    let _ = app.emit("update-progress", "downloading");
-
-    update
-        .download_and_install(|_chunk, _total| {}, || {})
-        .await
-        .map_err(|e| e.to_string())?;
+    log("install_update: starting");
+    updates::install(&app).await
+}
 
-    log("install_update: download and install complete");
-    let _ = app.emit("update-progress", "done");
+#[tauri::command]
+pub fn set_update_prefs(app: tauri::AppHandle, auto_check: bool, auto_install: bool) -> Result<(), ()> {
+    log(&format!("set_update_prefs: auto_check={}, auto_install={}", auto_check, auto_install));
+    settings::update(&app, |s| {
+        s.updates.auto_check = auto_check;
+        s.updates.auto_install = auto_install;
+    });
     Ok(())
 }
 
@@ -335,7 +862,10 @@ pub async fn open_url(url: String) -> Result<(), String> {
         return Err("Only HTTP/HTTPS URLs are allowed".to_string());
     }
     // Validate URL has a host after the scheme
-    let after_scheme = if url.starts_with("https://") { &url[8..] } else { &url[7..] };
+    let after_scheme = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or_default();
     if after_scheme.is_empty() || after_scheme.starts_with('/') {
         return Err("Invalid URL: missing host".to_string());
     }
@@ -0,0 +1,102 @@
+use crate::dock;
+use crate::events::{self, Event};
+use crate::log;
+use crate::settings::{self, WindowGeometry};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::Manager;
+
+/// Geometry is saved once the window has been still for this long, not on
+/// every event of a drag.
+const SAVE_DELAY_MS: u64 = 500;
+/// Bumped on every move or resize; a delayed save only runs if it is unchanged.
+static GEOMETRY_CHANGES: AtomicU64 = AtomicU64::new(0);
+
+/// Turn the panel into a free-floating, resizable window, where it was last
+/// left if that is still on a connected monitor.
+pub fn detach(app: &tauri::AppHandle) {
+    let Some(window) = app.get_webview_window(crate::PANEL_LABEL) else { return };
+    let stay_on_top = crate::STAY_ON_TOP_DETACHED.load(Ordering::SeqCst);
+    let _ = window.set_always_on_top(stay_on_top);
+    let _ = window.set_resizable(true);
+    let _ = window.set_min_size(Some(tauri::LogicalSize::new(300.0, 400.0)));
+
+    if let Some(geometry) = settings::get(app).panel.detached_geometry {
+        if on_screen(app, &geometry) {
+            log(&format!("detached: restoring {}x{} at ({}, {})", geometry.width, geometry.height, geometry.x, geometry.y));
+            let _ = window.set_size(tauri::PhysicalSize::new(geometry.width, geometry.height));
+            let _ = window.set_position(tauri::PhysicalPosition::new(geometry.x, geometry.y));
+        } else {
+            log("detached: saved position is off-screen, keeping the current one");
+        }
+    }
+
+    crate::PANEL_DETACHED.store(true, Ordering::SeqCst);
+    settings::update(app, |s| s.panel.detached = true);
+    events::emit(app, Event::PanelDetached);
+}
+
+/// Dock the panel again and hide it.
+pub fn attach(app: &tauri::AppHandle) {
+    let Some(window) = app.get_webview_window(crate::PANEL_LABEL) else { return };
+    let _ = window.set_always_on_top(true);
+    let _ = window.set_resizable(false);
+    let _ = window.set_min_size(None::<tauri::LogicalSize<f64>>);
+    let _ = window.set_size(tauri::LogicalSize::new(crate::PANEL_WIDTH, crate::PANEL_HEIGHT));
+    crate::PANEL_DETACHED.store(false, Ordering::SeqCst);
+    crate::PANEL_VISIBLE.store(false, Ordering::SeqCst);
+    let _ = window.hide();
+    settings::update(app, |s| s.panel.detached = false);
+    events::emit(app, Event::PanelAttached);
+}
+
+/// Whether a reasonable part of `geometry` lies on a connected monitor, so a
+/// window saved on an unplugged display isn't restored out of reach.
+fn on_screen(app: &tauri::AppHandle, geometry: &WindowGeometry) -> bool {
+    // The title-bar area, which has to be reachable to move the window back
+    let (x, y) = (geometry.x as f64 + 40.0, geometry.y as f64 + 10.0);
+    app.available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .any(|m| dock::monitor_rects(m).0.contains(x, y))
+}
+
+/// Note that the detached panel moved or was resized, saving its geometry
+/// once it settles.
+pub fn geometry_changed(app: &tauri::AppHandle) {
+    if !crate::PANEL_DETACHED.load(Ordering::SeqCst) {
+        return;
+    }
+    let change = GEOMETRY_CHANGES.fetch_add(1, Ordering::SeqCst) + 1;
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(SAVE_DELAY_MS)).await;
+        // Re-docking resizes the window too; that size isn't the detached one
+        if GEOMETRY_CHANGES.load(Ordering::SeqCst) == change && crate::PANEL_DETACHED.load(Ordering::SeqCst) {
+            save_geometry(&app);
+        }
+    });
+}
+
+fn save_geometry(app: &tauri::AppHandle) {
+    let Some(window) = app.get_webview_window(crate::PANEL_LABEL) else { return };
+    let (Ok(pos), Ok(size)) = (window.outer_position(), window.inner_size()) else { return };
+    let monitor = window.current_monitor().ok().flatten().and_then(|m| m.name().cloned());
+    let geometry = WindowGeometry { x: pos.x, y: pos.y, width: size.width, height: size.height, monitor };
+    log(&format!("detached: saving geometry {:?}", geometry));
+    settings::update(app, |s| s.panel.detached_geometry = Some(geometry));
+}
+
+/// Bring back detached mode if the panel was detached when the app quit.
+pub fn restore_at_startup(app: &tauri::AppHandle) {
+    let panel = settings::get(app).panel;
+    crate::STAY_ON_TOP_DETACHED.store(panel.stay_on_top, Ordering::SeqCst);
+    if !panel.detached {
+        return;
+    }
+    log("detached: restoring detached panel from last run");
+    detach(app);
+    if let Some(window) = app.get_webview_window(crate::PANEL_LABEL) {
+        let _ = window.show();
+        crate::PANEL_VISIBLE.store(true, Ordering::SeqCst);
+    }
+}
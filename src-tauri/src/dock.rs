@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+
+/// Gap between the panel and the taskbar or screen edge, in logical pixels.
+const MARGIN: f64 = 8.0;
+
+/// Where the docked panel is anchored on its monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    TopCenter,
+    /// Next to the tray icon, against the taskbar or menu bar.
+    NearTray,
+    BottomRight,
+}
+
+impl Default for Anchor {
+    /// Near the tray where its position is reported, else top-center.
+    fn default() -> Self {
+        if cfg!(target_os = "linux") {
+            Anchor::TopCenter
+        } else {
+            Anchor::NearTray
+        }
+    }
+}
+
+/// Screen edge holding the taskbar, dock or menu bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+}
+
+/// The edge the work area was shrunk from to make room for a taskbar, or the
+/// platform's usual one when the bar auto-hides or isn't reported.
+pub fn taskbar_edge(monitor: &Rect, work_area: &Rect) -> Edge {
+    // Tray icons live in the menu bar; the Dock shrinks the work area too
+    if cfg!(target_os = "macos") {
+        return Edge::Top;
+    }
+    let insets = [
+        (work_area.y - monitor.y, Edge::Top),
+        (monitor.bottom() - work_area.bottom(), Edge::Bottom),
+        (work_area.x - monitor.x, Edge::Left),
+        (monitor.right() - work_area.right(), Edge::Right),
+    ];
+    match insets.into_iter().filter(|(inset, _)| *inset > 0.0).max_by(|a, b| a.0.total_cmp(&b.0)) {
+        Some((_, edge)) => edge,
+        None if cfg!(target_os = "windows") => Edge::Bottom,
+        None => Edge::Top,
+    }
+}
+
+/// Top-left corner for a `panel` (width, height) anchored on a monitor with
+/// the given bounds, work area and scale factor, kept entirely inside the
+/// work area. `click` is where the tray icon was clicked, if known. Everything
+/// is in physical pixels, so monitors with different scale factors don't mix.
+pub fn place(
+    anchor: Anchor,
+    monitor: &Rect,
+    work_area: &Rect,
+    scale_factor: f64,
+    panel: (f64, f64),
+    click: Option<(f64, f64)>,
+) -> (f64, f64) {
+    let (w, h) = panel;
+    let margin = MARGIN * scale_factor;
+    let edge = taskbar_edge(monitor, work_area);
+    let top_center = (work_area.x + (work_area.width - w) / 2.0, work_area.y + margin);
+    let bottom_right = (work_area.right() - w - margin, work_area.bottom() - h - margin);
+
+    let (x, y) = match (anchor, click) {
+        (Anchor::TopCenter, _) => top_center,
+        (Anchor::BottomRight, _) => bottom_right,
+        (Anchor::NearTray, Some((cx, cy))) => match edge {
+            Edge::Top => (cx - w / 2.0, work_area.y + margin),
+            Edge::Bottom => (cx - w / 2.0, work_area.bottom() - h - margin),
+            Edge::Left => (work_area.x + margin, cy - h / 2.0),
+            Edge::Right => (work_area.right() - w - margin, cy - h / 2.0),
+        },
+        // No tray position: the corner where tray icons usually sit
+        (Anchor::NearTray, None) => match edge {
+            Edge::Top => (work_area.right() - w - margin, work_area.y + margin),
+            _ => bottom_right,
+        },
+    };
+
+    let clamp = |v: f64, lo: f64, hi: f64| v.min(hi).max(lo);
+    (
+        clamp(x, work_area.x, work_area.right() - w),
+        clamp(y, work_area.y, work_area.bottom() - h),
+    )
+}
+
+/// Bounds and work area of a monitor as [`Rect`]s.
+pub fn monitor_rects(monitor: &tauri::Monitor) -> (Rect, Rect) {
+    let (pos, size) = (monitor.position(), monitor.size());
+    let wa = monitor.work_area();
+    (
+        Rect { x: pos.x as f64, y: pos.y as f64, width: size.width as f64, height: size.height as f64 },
+        Rect {
+            x: wa.position.x as f64,
+            y: wa.position.y as f64,
+            width: wa.size.width as f64,
+            height: wa.size.height as f64,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: Rect = Rect { x: 0.0, y: 0.0, width: 1920.0, height: 1080.0 };
+    const PANEL: (f64, f64) = (360.0, 620.0);
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn taskbar_edge_is_where_the_work_area_shrank() {
+        let cases = [
+            (rect(0.0, 0.0, 1920.0, 1040.0), Edge::Bottom),
+            (rect(0.0, 32.0, 1920.0, 1048.0), Edge::Top),
+            (rect(60.0, 0.0, 1860.0, 1080.0), Edge::Left),
+            (rect(0.0, 0.0, 1860.0, 1080.0), Edge::Right),
+        ];
+        for (work_area, edge) in cases {
+            let expected = if cfg!(target_os = "macos") { Edge::Top } else { edge };
+            assert_eq!(taskbar_edge(&MONITOR, &work_area), expected, "{:?}", work_area);
+        }
+        let hidden = if cfg!(target_os = "windows") { Edge::Bottom } else { Edge::Top };
+        assert_eq!(taskbar_edge(&MONITOR, &MONITOR), hidden);
+    }
+
+    #[test]
+    fn fixed_anchors_sit_inside_the_work_area() {
+        let work_area = rect(0.0, 0.0, 1920.0, 1040.0);
+        assert_eq!(place(Anchor::TopCenter, &MONITOR, &work_area, 1.0, PANEL, None), (780.0, 8.0));
+        // The margin scales with the monitor
+        assert_eq!(
+            place(Anchor::BottomRight, &MONITOR, &work_area, 2.0, PANEL, Some((10.0, 10.0))),
+            (1920.0 - 360.0 - 16.0, 1040.0 - 620.0 - 16.0)
+        );
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn near_tray_follows_the_click_and_stays_on_screen() {
+        // Bottom taskbar, clicked near the right edge: pushed back inside
+        let bottom = rect(0.0, 0.0, 1920.0, 1040.0);
+        assert_eq!(place(Anchor::NearTray, &MONITOR, &bottom, 1.0, PANEL, Some((1900.0, 1060.0))), (1560.0, 412.0));
+        assert_eq!(place(Anchor::NearTray, &MONITOR, &bottom, 1.0, PANEL, Some((1000.0, 1060.0))), (820.0, 412.0));
+        // Left taskbar: beside it, centered on the click
+        let left = rect(60.0, 0.0, 1860.0, 1080.0);
+        assert_eq!(place(Anchor::NearTray, &MONITOR, &left, 1.0, PANEL, Some((30.0, 500.0))), (68.0, 190.0));
+        // No click position: the tray corner
+        assert_eq!(place(Anchor::NearTray, &MONITOR, &bottom, 1.0, PANEL, None), (1552.0, 412.0));
+    }
+}
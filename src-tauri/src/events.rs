@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tauri::Emitter;
+
+pub use crate::commands::{CostsResult, FormattedCosts, HistoryResult, UsageDisplay, UsageResult};
+
+/// Version of the event names and payloads below, including [`FIELDS`].
+/// Adding an optional field keeps it; renaming or removing anything, or changing a type, bumps it.
+pub const SCHEMA_VERSION: u32 = 2;
+
+pub const USAGE_READY: &str = "usage-ready";
+pub const COSTS_READY: &str = "costs-ready";
+pub const HISTORY_READY: &str = "history-ready";
+pub const PANEL_SHOWN: &str = "panel-shown";
+pub const PANEL_HIDDEN: &str = "panel-hidden";
+pub const PANEL_DETACHED: &str = "panel-detached";
+pub const PANEL_ATTACHED: &str = "panel-attached";
+pub const SNOOZE_CHANGED: &str = "snooze-changed";
+pub const UPDATE_PROGRESS: &str = "update-progress";
+pub const ACTIVITY_DETECTED: &str = "activity-detected";
+
+/// Payload of `update-progress`: "downloading", then "done".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateProgress {
+    Downloading,
+    Done,
+}
+
+/// Payload of `activity-detected`: Claude Code wrote to these project
+/// directories under `~/.claude/projects`, and fresh costs follow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityDetected {
+    pub projects: Vec<String>,
+}
+
+/// Every event the backend emits, with its payload. The `panel-*` events
+/// carry `null`.
+#[derive(Debug, Clone)]
+pub enum Event {
+    UsageReady(UsageResult),
+    CostsReady(CostsResult),
+    HistoryReady(HistoryResult),
+    PanelShown,
+    PanelHidden,
+    PanelDetached,
+    PanelAttached,
+    /// When the snooze ends in Unix seconds, or `null` once it is cleared.
+    SnoozeChanged(Option<i64>),
+    UpdateProgress(UpdateProgress),
+    ActivityDetected(ActivityDetected),
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::UsageReady(_) => USAGE_READY,
+            Event::CostsReady(_) => COSTS_READY,
+            Event::HistoryReady(_) => HISTORY_READY,
+            Event::PanelShown => PANEL_SHOWN,
+            Event::PanelHidden => PANEL_HIDDEN,
+            Event::PanelDetached => PANEL_DETACHED,
+            Event::PanelAttached => PANEL_ATTACHED,
+            Event::SnoozeChanged(_) => SNOOZE_CHANGED,
+            Event::UpdateProgress(_) => UPDATE_PROGRESS,
+            Event::ActivityDetected(_) => ACTIVITY_DETECTED,
+        }
+    }
+
+    /// The payload as it goes over the wire.
+    pub fn payload(&self) -> serde_json::Value {
+        let value = match self {
+            Event::UsageReady(p) => serde_json::to_value(p),
+            Event::CostsReady(p) => serde_json::to_value(p),
+            Event::HistoryReady(p) => serde_json::to_value(p),
+            Event::PanelShown | Event::PanelHidden | Event::PanelDetached | Event::PanelAttached => {
+                Ok(serde_json::Value::Null)
+            }
+            Event::SnoozeChanged(p) => serde_json::to_value(p),
+            Event::UpdateProgress(p) => serde_json::to_value(p),
+            Event::ActivityDetected(p) => serde_json::to_value(p),
+        };
+        value.unwrap_or(serde_json::Value::Null)
+    }
+}
+
+/// Names of all events, for `get_event_schema`.
+pub const ALL: &[&str] = &[
+    USAGE_READY,
+    COSTS_READY,
+    HISTORY_READY,
+    PANEL_SHOWN,
+    PANEL_HIDDEN,
+    PANEL_DETACHED,
+    PANEL_ATTACHED,
+    SNOOZE_CHANGED,
+    UPDATE_PROGRESS,
+    ACTIVITY_DETECTED,
+];
+
+/// Top-level fields of the events whose payload is an object. The others
+/// carry a bare value, as described on [`Event`].
+pub const FIELDS: &[(&str, &[&str])] = &[
+    (
+        USAGE_READY,
+        &[
+            "usage",
+            "usage_error",
+            "timestamp",
+            "rate_limited",
+            "stale",
+            "last_updated",
+            "cooldown_until",
+            "display",
+            "generation",
+        ],
+    ),
+    (COSTS_READY, &["costs", "costs_error", "formatted", "stale", "last_updated", "refreshing", "generation"]),
+    (HISTORY_READY, &["generation"]),
+    (ACTIVITY_DETECTED, &["projects"]),
+];
+
+/// What `get_event_schema` reports, so consumers can check compatibility.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventSchema {
+    pub version: u32,
+    pub events: Vec<String>,
+    /// Payload fields by event name, for the events with object payloads.
+    pub fields: BTreeMap<String, Vec<String>>,
+}
+
+pub fn schema() -> EventSchema {
+    EventSchema {
+        version: SCHEMA_VERSION,
+        events: ALL.iter().map(|e| e.to_string()).collect(),
+        fields: FIELDS
+            .iter()
+            .map(|(event, fields)| (event.to_string(), fields.iter().map(|f| f.to_string()).collect()))
+            .collect(),
+    }
+}
+
+/// Send `event` to the frontend.
+pub fn emit(app: &tauri::AppHandle, event: Event) {
+    if let Err(e) = app.emit(event.name(), event.payload()) {
+        crate::log_warn(&format!("events: emit {} failed: {}", event.name(), e));
+    }
+}
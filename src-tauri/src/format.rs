@@ -0,0 +1,117 @@
+use crate::settings::{FormatSettings, TokenUnits};
+
+/// Separators and currency placement for a locale, plus the user's unit choices.
+#[derive(Debug, Clone)]
+pub struct NumberFormat {
+    decimal: char,
+    group: char,
+    currency_after: bool,
+    currency_symbol: String,
+    token_units: TokenUnits,
+}
+
+/// "de_DE.UTF-8" from the environment, as "de-DE".
+fn system_locale() -> String {
+    ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty() && v != "C" && v != "POSIX")
+        .map(|v| v.split('.').next().unwrap_or("").replace('_', "-"))
+        .unwrap_or_default()
+}
+
+impl NumberFormat {
+    pub fn new(settings: &FormatSettings) -> Self {
+        let locale = if settings.locale.is_empty() { system_locale() } else { settings.locale.clone() };
+        let mut parts = locale.split('-');
+        let lang = parts.next().unwrap_or("").to_ascii_lowercase();
+        let region = parts.next().unwrap_or("").to_ascii_uppercase();
+
+        // (decimal, group, currency after the amount)
+        let (decimal, group, currency_after) = match (lang.as_str(), region.as_str()) {
+            ("de", "CH") => ('.', '\'', false),
+            ("de" | "es" | "it" | "da" | "el" | "tr" | "id", _) => (',', '.', true),
+            ("nl" | "pt", _) => (',', '.', false),
+            ("fr" | "ru" | "pl" | "sv" | "nb" | "no" | "fi" | "cs" | "sk" | "uk" | "hu", _) => {
+                (',', '\u{a0}', true)
+            }
+            _ => ('.', ',', false),
+        };
+
+        Self {
+            decimal,
+            group,
+            currency_after,
+            currency_symbol: settings.currency_symbol.clone(),
+            token_units: settings.token_units,
+        }
+    }
+
+    /// `value` with `decimals` fraction digits and grouped thousands.
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let fixed = format!("{:.*}", decimals, value.abs());
+        let (int_part, frac_part) = match fixed.split_once('.') {
+            Some((i, f)) => (i, Some(f)),
+            None => (fixed.as_str(), None),
+        };
+
+        let mut out = String::new();
+        if value < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            out.push('-');
+        }
+        for (i, c) in int_part.chars().enumerate() {
+            if i > 0 && (int_part.len() - i) % 3 == 0 {
+                out.push(self.group);
+            }
+            out.push(c);
+        }
+        if let Some(f) = frac_part {
+            out.push(self.decimal);
+            out.push_str(f);
+        }
+        out
+    }
+
+    pub fn currency(&self, value: f64) -> String {
+        let amount = self.number(value, 2);
+        if self.currency_after {
+            format!("{}\u{a0}{}", amount, self.currency_symbol)
+        } else {
+            format!("{}{}", self.currency_symbol, amount)
+        }
+    }
+
+    /// A 0.0 - 1.0 fraction as a whole percentage, rounded down like the panel.
+    pub fn percent(&self, fraction: f64) -> String {
+        format!("{}%", (fraction * 100.0).floor() as i64)
+    }
+
+    pub fn tokens(&self, count: f64) -> String {
+        if self.token_units == TokenUnits::Raw {
+            return self.number(count, 0);
+        }
+        let abs = count.abs();
+        let (scaled, suffix) = if abs >= 1e9 {
+            (count / 1e9, "B")
+        } else if abs >= 1e6 {
+            (count / 1e6, "M")
+        } else if abs >= 1e3 {
+            (count / 1e3, "K")
+        } else {
+            return self.number(count, 0);
+        };
+        let decimals = if scaled.abs() < 100.0 { 1 } else { 0 };
+        format!("{}{}", self.number(scaled, decimals), suffix)
+    }
+}
+
+/// The number format from the current settings.
+pub fn for_app(app: &tauri::AppHandle) -> NumberFormat {
+    NumberFormat::new(&crate::settings::get(app).format)
+}
+
+/// Local wall-clock time ("14:05") of a Unix timestamp.
+pub fn local_time(unix_secs: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(unix_secs, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%H:%M").to_string())
+}
@@ -0,0 +1,38 @@
+/// Whether the frontmost application is full-screen (a presentation, video call,
+/// game), so Claudit doesn't pop windows over it. Best effort and blocking; any
+/// failure to find out counts as "not full-screen".
+#[cfg(target_os = "macos")]
+pub fn foreground_is_fullscreen() -> bool {
+    // Requires Accessibility permission; without it osascript fails and we report false.
+    let script = r#"tell application "System Events" to get value of attribute "AXFullScreen" of front window of (first application process whose frontmost is true)"#;
+    match std::process::Command::new("osascript").args(["-e", script]).output() {
+        Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout).trim() == "true",
+        _ => false,
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn foreground_is_fullscreen() -> bool {
+    let xprop = |args: &[&str]| {
+        std::process::Command::new("xprop")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+    };
+    // "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007"
+    let window_id = match xprop(&["-root", "_NET_ACTIVE_WINDOW"])
+        .and_then(|s| s.split_whitespace().last().map(String::from))
+    {
+        Some(id) if id.starts_with("0x") && id != "0x0" => id,
+        _ => return false,
+    };
+    xprop(&["-id", &window_id, "_NET_WM_STATE"])
+        .is_some_and(|s| s.contains("_NET_WM_STATE_FULLSCREEN"))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+pub fn foreground_is_fullscreen() -> bool {
+    false
+}
@@ -0,0 +1,34 @@
+use crate::settings;
+use claudit_core::history;
+use claudit_core::storage::{self, WriteFailure};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Health {
+    pub ok: bool,
+    pub write_failures: Vec<WriteFailure>,
+    /// Usage snapshots held in memory until the history file is writable again.
+    pub pending_snapshots: usize,
+    /// Settings changes that are in effect but not yet saved to disk.
+    pub settings_unsaved: bool,
+}
+
+pub fn current() -> Health {
+    let write_failures = storage::write_failures();
+    let pending_snapshots = history::pending_count();
+    let settings_unsaved = settings::has_unsaved();
+    Health {
+        ok: write_failures.is_empty() && pending_snapshots == 0 && !settings_unsaved,
+        write_failures,
+        pending_snapshots,
+        settings_unsaved,
+    }
+}
+
+/// Retry every write that is waiting on the disk. Returns true once nothing is
+/// left pending.
+pub fn retry_pending_writes(app: &tauri::AppHandle) -> bool {
+    let history_ok = history::retry_pending();
+    let settings_ok = settings::retry_unsaved(app);
+    history_ok && settings_ok
+}
@@ -0,0 +1,92 @@
+use crate::dock::{self, Rect};
+use crate::settings::{self, Corner};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// Cursor poll interval while the trigger is enabled. Inside the corner the
+/// next poll comes when the dwell time is up, if that is sooner.
+const POLL_MS: u64 = 400;
+/// How often to look at the settings again, and how often to poll while the
+/// trigger is disabled.
+const SETTINGS_REFRESH: Duration = Duration::from_secs(2);
+/// How often to list the monitors again, to pick up ones plugged in or moved.
+const MONITORS_REFRESH: Duration = Duration::from_secs(10);
+/// Size of the corner hit box, in logical pixels.
+const CORNER_SIZE: f64 = 4.0;
+
+/// Whether physical point (`x`, `y`) is in `corner` of `monitor`.
+pub fn in_corner(corner: Corner, monitor: &Rect, scale_factor: f64, x: f64, y: f64) -> bool {
+    if !monitor.contains(x, y) {
+        return false;
+    }
+    let size = CORNER_SIZE * scale_factor;
+    let near_left = x < monitor.x + size;
+    let near_right = x >= monitor.x + monitor.width - size;
+    let near_top = y < monitor.y + size;
+    let near_bottom = y >= monitor.y + monitor.height - size;
+    match corner {
+        Corner::TopLeft => near_top && near_left,
+        Corner::TopRight => near_top && near_right,
+        Corner::BottomLeft => near_bottom && near_left,
+        Corner::BottomRight => near_bottom && near_right,
+    }
+}
+
+/// Poll the cursor and show the docked panel when it rests in the configured
+/// corner of any monitor for the dwell time. Runs for the life of the app.
+pub async fn run(app: tauri::AppHandle) {
+    let mut entered: Option<Instant> = None;
+    // Fire once per visit to the corner
+    let mut fired = false;
+    let mut prefs = None;
+    let mut prefs_read: Option<Instant> = None;
+    // Bounds and scale factor of each monitor
+    let mut monitors: Vec<(Rect, f64)> = Vec::new();
+    let mut monitors_read: Option<Instant> = None;
+    loop {
+        if prefs_read.is_none_or(|t| t.elapsed() >= SETTINGS_REFRESH) {
+            prefs = settings::get(&app).hot_corner;
+            prefs_read = Some(Instant::now());
+        }
+        let Some(ref hot_corner) = prefs else {
+            entered = None;
+            monitors_read = None;
+            tokio::time::sleep(SETTINGS_REFRESH).await;
+            continue;
+        };
+        let dwell = Duration::from_millis(hot_corner.dwell_ms);
+        let wait = match entered {
+            Some(since) if !fired => dwell.saturating_sub(since.elapsed()).min(Duration::from_millis(POLL_MS)),
+            _ => Duration::from_millis(POLL_MS),
+        };
+        tokio::time::sleep(wait).await;
+
+        if monitors_read.is_none_or(|t| t.elapsed() >= MONITORS_REFRESH) {
+            monitors = app
+                .available_monitors()
+                .unwrap_or_default()
+                .iter()
+                .map(|m| (dock::monitor_rects(m).0, m.scale_factor()))
+                .collect();
+            monitors_read = Some(Instant::now());
+        }
+        let in_any = app.cursor_position().is_ok_and(|p| {
+            monitors
+                .iter()
+                .any(|(bounds, scale)| in_corner(hot_corner.corner, bounds, *scale, p.x, p.y))
+        });
+        if !in_any {
+            entered = None;
+            fired = false;
+            continue;
+        }
+        let since = *entered.get_or_insert_with(Instant::now);
+        if !fired && since.elapsed() >= dwell {
+            fired = true;
+            if !crate::PANEL_VISIBLE.load(Ordering::SeqCst) {
+                crate::log("hot corner: showing panel");
+                crate::show_panel(&app, None);
+            }
+        }
+    }
+}
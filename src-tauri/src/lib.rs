@@ -1,16 +1,34 @@
-mod ccusage;
+mod alerts;
+mod cli;
 mod commands;
+mod detached;
+mod diagnostics;
+mod dock;
+pub mod events;
+mod format;
+mod fullscreen;
+mod health;
 mod history;
-mod keychain;
+mod hotcorner;
+mod logging;
 mod notifier;
-mod usage_api;
+mod preflight;
+mod settings;
+mod simulate;
+mod storage;
+mod templates;
+mod tray;
+mod updates;
+mod watcher;
+
+#[cfg(feature = "test-hooks")]
+pub use commands::{collect_usage, collect_usage_from, LastUsage, UsageResult};
 
-use std::io::Write;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use tauri::{
-    menu::{MenuBuilder, MenuItemBuilder},
+    menu::{MenuBuilder, MenuItemBuilder, SubmenuBuilder},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    Emitter, Manager, PhysicalPosition, WebviewUrl, WebviewWindowBuilder,
+    Manager, PhysicalPosition, WebviewUrl, WebviewWindowBuilder,
 };
 
 const PANEL_LABEL: &str = "panel";
@@ -27,30 +45,17 @@ pub static STAY_ON_TOP_DETACHED: AtomicBool = AtomicBool::new(false);
 static LAST_BLUR_HIDE_MS: AtomicU64 = AtomicU64::new(0);
 
 pub fn log(msg: &str) {
-    let log_dir = dirs::data_dir()
-        .unwrap_or_else(std::env::temp_dir)
-        .join("com.claudit.monitor");
-    let _ = std::fs::create_dir_all(&log_dir);
-    let log_path = log_dir.join("debug.log");
+    logging::write(logging::Level::Info, msg);
+}
 
-    #[cfg(unix)]
-    let file = {
-        use std::os::unix::fs::OpenOptionsExt;
-        std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .mode(0o600)
-            .open(&log_path)
-    };
-    #[cfg(not(unix))]
-    let file = std::fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_path);
+/// Routine detail, only written at the `debug` log level.
+pub fn log_debug(msg: &str) {
+    logging::write(logging::Level::Debug, msg);
+}
 
-    if let Ok(mut f) = file {
-        let _ = writeln!(f, "[{}] {}", chrono::Local::now().format("%H:%M:%S%.3f"), msg);
-    }
+/// Something failed but the app carries on.
+pub fn log_warn(msg: &str) {
+    logging::write(logging::Level::Warn, msg);
 }
 
 fn show_panel(app: &tauri::AppHandle, cursor_pos: Option<PhysicalPosition<f64>>) {
@@ -63,67 +68,87 @@ fn show_panel(app: &tauri::AppHandle, cursor_pos: Option<PhysicalPosition<f64>>)
             let _ = w.show();
             let _ = w.set_focus();
             PANEL_VISIBLE.store(true, Ordering::SeqCst);
-            let _ = app.emit("panel-shown", ());
+            events::emit(app, events::Event::PanelShown);
             return;
         }
 
         if let Some(pos) = cursor_pos {
-            log(&format!("Tray click at physical ({}, {})", pos.x, pos.y));
-            if let Ok(monitors) = app.available_monitors() {
-                for mon in monitors {
-                    let mpos = mon.position();
-                    let size = mon.size();
-                    let sf = mon.scale_factor();
-                    // Convert physical click position to logical
-                    let cx = pos.x / sf;
-                    let cy = pos.y / sf;
-                    let mx = mpos.x as f64 / sf;
-                    let my = mpos.y as f64 / sf;
-                    let mw = size.width as f64 / sf;
-                    let mh = size.height as f64 / sf;
-                    log(&format!("Monitor: logical ({}, {}) {}x{} sf={}", mx, my, mw, mh, sf));
-                    if cx >= mx && cx < mx + mw && cy >= my && cy < my + mh {
-                        let x = (cx - PANEL_WIDTH / 2.0).max(mx).min(mx + mw - PANEL_WIDTH);
-                        let y = my + 30.0;
-                        log(&format!("Moving panel to ({}, {})", x, y));
-                        let _ = w.set_position(tauri::Position::Logical(tauri::LogicalPosition::new(x, y)));
-                        break;
-                    }
-                }
-            }
-        } else {
-            // Fallback: center on primary monitor (e.g. Linux where tray position may be unavailable)
-            log("No cursor position, centering on primary monitor");
-            if let Ok(Some(mon)) = app.primary_monitor() {
+            log_debug(&format!("Tray click at physical ({}, {})", pos.x, pos.y));
+        }
+        // The monitor under the tray click, else under the mouse, else the primary one
+        let point = cursor_pos.or_else(|| app.cursor_position().ok());
+        let monitor = point
+            .and_then(|p| {
+                app.available_monitors()
+                    .ok()?
+                    .into_iter()
+                    .find(|m| dock::monitor_rects(m).0.contains(p.x, p.y))
+            })
+            .or_else(|| app.primary_monitor().ok().flatten());
+        match monitor {
+            Some(mon) => {
+                let (bounds, work_area) = dock::monitor_rects(&mon);
                 let sf = mon.scale_factor();
-                let mw = mon.size().width as f64 / sf;
-                let x = (mw - PANEL_WIDTH) / 2.0;
-                let _ = w.set_position(tauri::Position::Logical(tauri::LogicalPosition::new(x, 30.0)));
+                let anchor = settings::get(app).panel.anchor;
+                let (x, y) = dock::place(
+                    anchor,
+                    &bounds,
+                    &work_area,
+                    sf,
+                    (PANEL_WIDTH * sf, PANEL_HEIGHT * sf),
+                    cursor_pos.map(|p| (p.x, p.y)),
+                );
+                log_debug(&format!("Moving panel to physical ({}, {}), anchor {:?}, sf={}", x, y, anchor, sf));
+                let _ = w.set_position(tauri::Position::Physical(tauri::PhysicalPosition::new(
+                    x.round() as i32,
+                    y.round() as i32,
+                )));
             }
+            None => log("No monitor found, leaving panel where it is"),
         }
 
         let _ = w.show();
         let _ = w.set_focus();
         PANEL_VISIBLE.store(true, Ordering::SeqCst);
-        let _ = app.emit("panel-shown", ());
-        log(&format!("Panel shown, visible={:?}", w.is_visible()));
+        events::emit(app, events::Event::PanelShown);
+        log_debug(&format!("Panel shown, visible={:?}", w.is_visible()));
     } else {
-        log("ERROR: panel window not found!");
+        log_warn("panel window not found");
     }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    claudit_core::set_logger(log);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match cli::parse(&args) {
+        Ok(Some(request)) => {
+            cli::attach_console();
+            log(&format!("Headless: {:?}", request));
+            let code = cli::run(request);
+            logging::flush();
+            std::process::exit(code);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            cli::attach_console();
+            eprintln!("claudit: {}\n\n{}", e, cli::HELP);
+            std::process::exit(2);
+        }
+    }
     log("App starting");
 
     tauri::Builder::default()
-        .manage(ccusage::CostCache::new())
         .plugin(tauri_plugin_autostart::init(tauri_plugin_autostart::MacosLauncher::LaunchAgent, None))
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             commands::get_usage_data,
             commands::get_costs_data,
+            commands::get_all_data,
+            commands::get_account_info,
             commands::hide_panel,
             commands::detach_panel,
             commands::attach_panel,
@@ -132,14 +157,44 @@ pub fn run() {
             commands::set_autostart_enabled,
             commands::check_for_updates,
             commands::install_update,
+            commands::set_update_prefs,
             commands::relaunch_app,
             commands::open_login,
             commands::open_url,
             commands::set_notify_tokens_pref,
+            commands::snooze_notifications,
+            commands::get_settings,
+            commands::get_event_schema,
+            commands::set_admin_api_key,
+            commands::has_admin_api_key,
+            commands::set_proxy_password,
+            commands::update_settings,
+            commands::get_notification_history,
+            commands::simulate_rules,
+            commands::get_burn_rate,
+            commands::get_health,
+            commands::get_forecast,
+            commands::get_project_usage,
+            commands::get_sessions,
+            commands::get_usage_series,
+            commands::compact_history,
+            commands::run_preflight,
+            commands::get_exhaustion_events,
+            commands::generate_diagnostics,
         ])
         .setup(|app| {
             log("Setup starting");
 
+            // Backed by a file so a cold start can show the last costs immediately
+            let cost_cache = match storage::app_data_file(app.handle(), "cost_cache.json") {
+                Some(path) => claudit_core::ccusage::CostCache::with_file(path),
+                None => claudit_core::ccusage::CostCache::new(),
+            };
+            app.manage(cost_cache);
+
+            // Loading the settings also applies the history retention policy
+            settings::get(app.handle());
+
             #[cfg(target_os = "macos")]
             {
                 app.set_activation_policy(tauri::ActivationPolicy::Accessory);
@@ -167,20 +222,39 @@ pub fn run() {
                 .skip_taskbar(true)
                 .build()?;
             log(&format!("Panel window created at ({}, {}), visible={:?}", x, y, window.is_visible()));
+            detached::restore_at_startup(app.handle());
 
             // Build tray menu (right-click only)
+            let summary = tray::SummaryItems::build(app)?;
+            let copy_item = MenuItemBuilder::with_id("copy-summary", "Copy Summary").build(app)?;
+            let update_item = updates::UpdateMenuItem::build(app)?;
+            let snooze_menu = SubmenuBuilder::new(app, "Snooze Notifications")
+                .text("snooze-1h", "For 1 Hour")
+                .text("snooze-4h", "For 4 Hours")
+                .text("snooze-today", "Until Tomorrow")
+                .separator()
+                .text("snooze-off", "Resume")
+                .build()?;
             let refresh_item = MenuItemBuilder::with_id("refresh", "Refresh").build(app)?;
             let quit_item = MenuItemBuilder::with_id("quit", "Quit Claudit").build(app)?;
 
+            let [session_item, week_item, opus_item, today_item] = summary.items();
             let menu = MenuBuilder::new(app)
+                .items(&[session_item, week_item, opus_item, today_item])
+                .item(&copy_item)
+                .separator()
+                .item(&snooze_menu)
                 .item(&refresh_item)
+                .item(update_item.item())
                 .separator()
                 .item(&quit_item)
                 .build()?;
+            app.manage(summary);
+            app.manage(update_item);
 
             let icon = tauri::include_image!("icons/tray-icon.png");
 
-            let _tray = TrayIconBuilder::with_id("main-tray")
+            let _tray = TrayIconBuilder::with_id(tray::TRAY_ID)
                 .icon(icon)
                 .icon_as_template(true)
                 .tooltip("Claudit")
@@ -213,11 +287,23 @@ pub fn run() {
                     }
                 })
                 .on_menu_event(|app, event| {
-                    log(&format!("Menu event: {:?}", event.id()));
+                    log_debug(&format!("Menu event: {:?}", event.id()));
                     match event.id().as_ref() {
+                        "copy-summary" => {
+                            tray::copy_summary(app);
+                        }
                         "refresh" => {
                             show_panel(app, None);
                         }
+                        "update" => {
+                            updates::menu_clicked(app);
+                        }
+                        id if id.starts_with("snooze-") => {
+                            let duration = id.strip_prefix("snooze-").filter(|d| *d != "off");
+                            if let Err(e) = notifier::snooze(app, duration) {
+                                log_warn(&format!("Snooze failed: {}", e));
+                            }
+                        }
                         "quit" => {
                             log("Quitting");
                             app.exit(0);
@@ -230,19 +316,52 @@ pub fn run() {
             log("Tray icon with menu created, setup complete");
 
             // Spawn background notification checker (independent of panel visibility)
-            tauri::async_runtime::spawn(async {
+            let notifier_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
                 // Let the app settle before starting checks
                 tokio::time::sleep(std::time::Duration::from_secs(120)).await;
                 loop {
-                    notifier::check_and_notify().await;
+                    notifier::check_and_notify(&notifier_handle).await;
+                    // Keeps today's cost in the tray menu current with the panel closed,
+                    // from the last ccusage run rather than starting a new one
+                    let cost_cache = notifier_handle.state::<claudit_core::ccusage::CostCache>();
+                    if let Some(cached) = cost_cache.get_any() {
+                        tray::update_costs(&notifier_handle, &cached.data);
+                    }
                     tokio::time::sleep(std::time::Duration::from_secs(300)).await;
                 }
             });
 
+            tauri::async_runtime::spawn(hotcorner::run(app.handle().clone()));
+            tauri::async_runtime::spawn(updates::run(app.handle().clone()));
+            tauri::async_runtime::spawn(watcher::run(app.handle().clone()));
+
+            // Scheduled pre-flight check, looked at once a minute so settings changes apply
+            let preflight_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    preflight::tick(&preflight_handle).await;
+                    tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                }
+            });
+
+            // Retry writes that failed (full or read-only disk), backing off while they keep failing
+            let retry_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut delay = 30;
+                loop {
+                    tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+                    delay = if health::retry_pending_writes(&retry_handle) { 30 } else { (delay * 2).min(1800) };
+                }
+            });
+
             Ok(())
         })
         .on_window_event(|window, event| {
             if window.label() == PANEL_LABEL {
+                if let tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) = event {
+                    detached::geometry_changed(window.app_handle());
+                }
                 if let tauri::WindowEvent::Focused(false) = event {
                     // Skip blur-hide when panel is detached
                     if PANEL_DETACHED.load(Ordering::SeqCst) {
@@ -257,12 +376,18 @@ pub fn run() {
                             .unwrap_or_default()
                             .as_millis() as u64;
                         LAST_BLUR_HIDE_MS.store(now_ms, Ordering::SeqCst);
-                        let _ = window.app_handle().emit("panel-hidden", ());
+                        events::emit(window.app_handle(), events::Event::PanelHidden);
                         log("Panel hidden on blur");
                     }
                 }
             }
         })
-        .run(tauri::generate_context!())
-        .expect("error while running application");
+        .build(tauri::generate_context!())
+        .expect("error while running application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                log("Exiting");
+                logging::flush();
+            }
+        });
 }
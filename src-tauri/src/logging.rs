@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Buffered lines are written out at least this often.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+/// Lines waiting for the writer before new ones are dropped, so a burst of
+/// logging can't pile up in memory or hold up the caller.
+const QUEUE_LIMIT: usize = 1024;
+/// Write early once this many lines are buffered.
+const BATCH_LINES: usize = 64;
+
+/// How much detail goes into the debug log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn tag(self) -> &'static str {
+        match self {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        }
+    }
+}
+
+enum Message {
+    Line(String),
+    /// Write everything queued so far, then reply.
+    Flush(mpsc::Sender<()>),
+}
+
+static SENDER: OnceLock<SyncSender<Message>> = OnceLock::new();
+/// Lines dropped because the queue was full, reported with the next batch.
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+/// Lines below this level are discarded before they are queued.
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+/// The log is rotated once it grows past this many bytes.
+static MAX_BYTES: AtomicU64 = AtomicU64::new(1024 * 1024);
+/// Rotated logs kept next to the current one (debug.1.log, debug.2.log, ...).
+static KEEP_FILES: AtomicU64 = AtomicU64::new(3);
+
+/// Apply the log settings: the lowest level written, and the size and number
+/// of rotated files.
+pub fn configure(level: Level, max_kb: u64, keep_files: u64) {
+    MIN_LEVEL.store(level as u8, Ordering::SeqCst);
+    MAX_BYTES.store(max_kb * 1024, Ordering::SeqCst);
+    KEEP_FILES.store(keep_files, Ordering::SeqCst);
+}
+
+pub fn log_dir() -> PathBuf {
+    let log_dir = dirs::data_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("com.claudit.monitor");
+    let _ = std::fs::create_dir_all(&log_dir);
+    log_dir
+}
+
+/// The current log followed by the rotated ones, newest first, that exist.
+pub fn log_files() -> Vec<PathBuf> {
+    let dir = log_dir();
+    let rotated = (1..=KEEP_FILES.load(Ordering::SeqCst)).map(|n| dir.join(format!("debug.{}.log", n)));
+    std::iter::once(dir.join("debug.log")).chain(rotated).filter(|p| p.exists()).collect()
+}
+
+fn open() -> Option<(BufWriter<File>, u64)> {
+    let path = log_dir().join("debug.log");
+    #[cfg(unix)]
+    let file = {
+        use std::os::unix::fs::OpenOptionsExt;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(&path)
+    };
+    #[cfg(not(unix))]
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(&path);
+    let file = file.ok()?;
+    let size = file.metadata().map_or(0, |m| m.len());
+    Some((BufWriter::new(file), size))
+}
+
+/// Shift debug.log to debug.1.log, debug.1.log to debug.2.log and so on,
+/// dropping the oldest.
+fn rotate() {
+    let dir = log_dir();
+    let keep = KEEP_FILES.load(Ordering::SeqCst);
+    let name = |n: u64| match n {
+        0 => dir.join("debug.log"),
+        n => dir.join(format!("debug.{}.log", n)),
+    };
+    if keep == 0 {
+        let _ = std::fs::remove_file(name(0));
+        return;
+    }
+    let _ = std::fs::remove_file(name(keep));
+    for n in (0..keep).rev() {
+        let _ = std::fs::rename(name(n), name(n + 1));
+    }
+}
+
+/// Keeps the log file open, writes queued lines in batches and rotates the
+/// file when it gets too big.
+fn writer(rx: Receiver<Message>) {
+    let mut out: Option<(BufWriter<File>, u64)> = None;
+    let mut pending = 0;
+    // When the oldest unflushed line has to be on disk, so a steady trickle
+    // of lines can't hold it back
+    let mut deadline: Option<Instant> = None;
+    loop {
+        let received = match deadline {
+            Some(d) => rx.recv_timeout(d.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let (line, reply) = match received {
+            Ok(Message::Line(line)) => (Some(line), None),
+            Ok(Message::Flush(reply)) => (None, Some(reply)),
+            Err(RecvTimeoutError::Timeout) => (None, None),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if let Some(line) = line {
+            if out.as_ref().is_some_and(|(_, size)| *size >= MAX_BYTES.load(Ordering::SeqCst)) {
+                if let Some((mut f, _)) = out.take() {
+                    let _ = f.flush();
+                }
+                rotate();
+            }
+            if out.is_none() {
+                out = open();
+            }
+            if let Some((ref mut f, ref mut size)) = out {
+                let dropped = DROPPED.swap(0, Ordering::SeqCst);
+                if dropped > 0 {
+                    let _ = writeln!(f, "[logging] dropped {} lines", dropped);
+                }
+                let _ = writeln!(f, "{}", line);
+                *size += line.len() as u64 + 1;
+                pending += 1;
+                deadline.get_or_insert_with(|| Instant::now() + FLUSH_INTERVAL);
+            }
+            if pending < BATCH_LINES && deadline.is_some_and(|d| Instant::now() < d) {
+                continue;
+            }
+        }
+        if let Some((ref mut f, _)) = out {
+            // Reopen on the next line if the file went away
+            if f.flush().is_err() {
+                out = None;
+            }
+        }
+        pending = 0;
+        deadline = None;
+        if let Some(reply) = reply {
+            let _ = reply.send(());
+        }
+    }
+}
+
+fn sender() -> &'static SyncSender<Message> {
+    SENDER.get_or_init(|| {
+        let (tx, rx) = mpsc::sync_channel(QUEUE_LIMIT);
+        std::thread::Builder::new()
+            .name("log-writer".into())
+            .spawn(move || writer(rx))
+            .expect("failed to start the log writer");
+        tx
+    })
+}
+
+/// Queue a message for the debug log if `level` is enabled. Never blocks;
+/// lines are dropped (and counted) while the writer is behind.
+pub fn write(level: Level, msg: &str) {
+    if (level as u8) < MIN_LEVEL.load(Ordering::SeqCst) {
+        return;
+    }
+    let time = chrono::Local::now().format("%H:%M:%S%.3f");
+    let line = match level {
+        Level::Info => format!("[{}] {}", time, msg),
+        _ => format!("[{}] [{}] {}", time, level.tag(), msg),
+    };
+    match sender().try_send(Message::Line(line)) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+            DROPPED.fetch_add(1, Ordering::SeqCst);
+        }
+        Err(TrySendError::Disconnected(_)) => {}
+    }
+}
+
+/// Write out everything logged so far, waiting up to a second. Called on exit.
+pub fn flush() {
+    let (tx, rx) = mpsc::channel();
+    if sender().send(Message::Flush(tx)).is_ok() {
+        let _ = rx.recv_timeout(Duration::from_secs(1));
+    }
+}
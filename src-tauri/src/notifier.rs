@@ -1,44 +1,110 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
 use std::sync::Mutex;
 
-use crate::keychain;
-use crate::usage_api;
-
-pub static NOTIFICATIONS_ENABLED: AtomicBool = AtomicBool::new(true);
-
-/// Tracks the `reset_at` value we last notified for, so we only fire once per session window.
-static LAST_NOTIFIED_RESET: Mutex<Option<String>> = Mutex::new(None);
-
-pub async fn check_and_notify() {
-    if !NOTIFICATIONS_ENABLED.load(Ordering::SeqCst) {
-        return;
-    }
-
-    let token = match tokio::task::spawn_blocking(keychain::get_oauth_token).await {
-        Ok(Ok(t)) => t,
-        _ => {
+use chrono::{DateTime, Days, Local, Utc};
+
+use crate::alerts::{self, Alert};
+use crate::events::{self, Event};
+use crate::format::{self, NumberFormat};
+use crate::fullscreen;
+use crate::history;
+use crate::settings::{self, RuleSettings};
+use crate::templates::{self, TemplateText};
+use claudit_core::burn_rate;
+use claudit_core::history::UsageSnapshot;
+use claudit_core::token;
+use claudit_core::usage_api::{self, UsageData, UsageError};
+
+/// Keys of critical conditions the panel has already been auto-shown for.
+static AUTO_SHOWN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub async fn check_and_notify(app: &tauri::AppHandle) {
+    let data = match token::fetch_usage_from(&settings::get(app).usage_source()).await {
+        Ok(d) => d,
+        Err(UsageError::Credentials(_)) => {
             crate::log("notifier: no valid token, skipping");
             return;
         }
-    };
-
-    let data = match usage_api::fetch_usage(&token).await {
-        Ok(d) => d,
-        Err(usage_api::UsageError::RateLimited) => {
+        Err(UsageError::RateLimited) => {
             crate::log("notifier: rate-limited, skipping check");
+            if let Some(until) = usage_api::cooldown_until() {
+                crate::tray::show_cooldown(app, until, crate::commands::last_usage(app).as_ref());
+            }
             return;
         }
         Err(e) => {
-            crate::log(&format!("notifier: fetch_usage error: {}", e));
+            crate::log_warn(&format!("notifier: fetch_usage error: {}", e));
             return;
         }
     };
 
-    let session = match data.limits.iter().find(|l| l.label == "Current session") {
+    // Keep the tray ring current even while the panel is closed
+    crate::tray::update(app, &data);
+    maybe_auto_show(app, &data).await;
+
+    let prefs = settings::get(app);
+    if !prefs.notifications.enabled {
+        return;
+    }
+    if let Some(reason) = prefs.muted_reason() {
+        crate::log(&format!("notifier: {}, not checking rules", reason));
+        return;
+    }
+    let snapshots = history::load_history(app).snapshots;
+    let fmt = format::for_app(app);
+    for alert in evaluate_rules(&data, &snapshots, Utc::now(), &prefs.rules, &fmt, &prefs.templates) {
+        crate::log(&format!("notifier: firing {}", alert.id));
+        alerts::dispatch(app, alert).await;
+    }
+}
+
+/// Unix time a snooze of `duration` ("1h", "4h" or "today") taken at `now`
+/// runs until. "today" ends at the next local midnight.
+pub fn snooze_until(duration: &str, now: DateTime<Local>) -> Result<i64, String> {
+    match duration {
+        "1h" => Ok(now.timestamp() + 3600),
+        "4h" => Ok(now.timestamp() + 4 * 3600),
+        "today" => (now.date_naive() + Days::new(1))
+            .and_hms_opt(0, 0, 0)
+            .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+            .map(|midnight| midnight.timestamp())
+            .ok_or_else(|| "Could not work out local midnight".to_string()),
+        other => Err(format!("Unknown snooze duration \"{}\"", other)),
+    }
+}
+
+/// Hold back all notifications for `duration`, or resume them with `None`.
+/// Returns when the snooze ends.
+pub fn snooze(app: &tauri::AppHandle, duration: Option<&str>) -> Result<Option<i64>, String> {
+    let until = duration.map(|d| snooze_until(d, Local::now())).transpose()?;
+    match until.and_then(format::local_time) {
+        Some(time) => crate::log(&format!("notifier: snoozed until {}", time)),
+        None => crate::log("notifier: snooze cleared"),
+    }
+    settings::update(app, |s| s.notifications.snoozed_until = until);
+    events::emit(app, Event::SnoozeChanged(until));
+    Ok(until)
+}
+
+/// Alerts the notification rules raise for `data` observed at `now`, with
+/// `snapshots` as the recent history. Pure, so the same rules drive both live
+/// checks and [`crate::simulate`].
+pub fn evaluate_rules(
+    data: &UsageData,
+    snapshots: &[UsageSnapshot],
+    now: DateTime<Utc>,
+    rules: &RuleSettings,
+    fmt: &NumberFormat,
+    templates: &HashMap<String, TemplateText>,
+) -> Vec<Alert> {
+    let mut fired = Vec::new();
+
+    let session = match data.limits.iter().find(|l| l.label == burn_rate::SESSION_LABEL) {
         Some(s) => s,
         None => {
             crate::log("notifier: no session limit found");
-            return;
+            return fired;
         }
     };
 
@@ -46,65 +112,129 @@ pub async fn check_and_notify() {
         Some(r) => r.clone(),
         None => {
             crate::log("notifier: no reset_at on session");
-            return;
+            return fired;
         }
     };
 
-    // Check if we already notified for this session window
-    {
-        let guard = LAST_NOTIFIED_RESET.lock().unwrap();
-        if guard.as_deref() == Some(&reset_at_str) {
-            return; // already notified for this window
-        }
-    }
-
-    let reset_at = match chrono::DateTime::parse_from_rfc3339(&reset_at_str) {
+    let reset_at = match DateTime::parse_from_rfc3339(&reset_at_str) {
         Ok(dt) => dt,
         Err(_) => {
-            crate::log(&format!("notifier: failed to parse reset_at: {}", reset_at_str));
-            return;
+            crate::log_warn(&format!("notifier: failed to parse reset_at: {}", reset_at_str));
+            return fired;
         }
     };
 
-    let now = chrono::Utc::now();
-    let until_reset = reset_at.signed_duration_since(now);
-    let minutes_left = until_reset.num_minutes();
-
+    let minutes_left = reset_at.signed_duration_since(now).num_minutes();
     let usage_pct = session.usage_pct; // 0.0 - 1.0
 
-    crate::log(&format!(
-        "notifier: {}min until reset, usage={:.0}%",
-        minutes_left,
-        usage_pct * 100.0
-    ));
-
     // Trigger conditions:
-    // - 30-75 minutes until reset
-    // - Usage below 80% (at least 20% going unused)
-    if minutes_left >= 30 && minutes_left <= 75 && usage_pct < 0.80 {
-        let pct = (usage_pct * 100.0).floor() as i32;
-        let unused = 100 - pct;
+    // - Session resets within the configured window (default 30-75 minutes)
+    // - Usage below the threshold (default 80%, i.e. at least 20% going unused)
+    if (rules.unused_min_minutes..=rules.unused_max_minutes).contains(&minutes_left)
+        && usage_pct < rules.unused_below_pct
+    {
+        let (title, body) = templates::render(
+            &templates::UNUSED_TOKENS,
+            templates,
+            &[("used_pct", fmt.percent(usage_pct)), ("minutes_left", minutes_left.to_string())],
+        );
 
-        crate::log(&format!("notifier: firing notification ({}% unused, {}min left)", unused, minutes_left));
+        // Keyed by session window, so every channel fires once per window
+        fired.push(Alert {
+            id: format!("unused-tokens:{}", reset_at_str),
+            kind: templates::UNUSED_TOKENS.id.into(),
+            title,
+            body,
+        });
+    }
 
-        let body = format!(
-            "You've only used {}% of your session. ~{}min left before it resets.",
-            pct, minutes_left
-        );
+    let rate = burn_rate::compute(snapshots, session, now);
+    if let (Some(minutes), Some(to_reset)) = (rate.minutes_to_exhaustion, rate.minutes_to_reset) {
+        if minutes <= rules.burn_warn_minutes {
+            let (title, body) = templates::render(
+                &templates::BURN_RATE,
+                templates,
+                &[
+                    ("minutes_to_limit", minutes.to_string()),
+                    ("minutes_early", (to_reset - minutes).to_string()),
+                ],
+            );
+            fired.push(Alert {
+                id: format!("burn-rate:{}", reset_at_str),
+                kind: templates::BURN_RATE.id.into(),
+                title,
+                body,
+            });
+        }
+    }
+    fired
+}
 
-        let result = notify_rust::Notification::new()
-            .summary("Use your tokens!")
-            .body(&body)
-            .appname("Claudit")
-            .show();
+/// Critical conditions in `data` as (key, description). Keys include the window
+/// they belong to, so each condition pops the panel once per window.
+pub fn critical_conditions(data: &UsageData, now: DateTime<Utc>) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    for limit in data.limits.iter().filter(|l| l.usage_pct >= 1.0) {
+        found.push((
+            format!("limit:{}:{}", limit.label, limit.reset_at.as_deref().unwrap_or("")),
+            format!("{} limit reached", limit.label),
+        ));
+    }
+    if let Some(ref eu) = data.extra_usage {
+        if eu.utilization >= 1.0 {
+            found.push((
+                format!("extra-usage:{}", now.with_timezone(&chrono::Local).format("%Y-%m")),
+                "Extra usage budget exceeded".to_string(),
+            ));
+        }
+    }
+    found
+}
 
-        match result {
-            Ok(_) => crate::log("notifier: notification sent"),
-            Err(e) => crate::log(&format!("notifier: failed to send: {}", e)),
+/// Show the panel when a limit or budget has just run out, if the user opted in.
+/// Conditions hit during quiet hours or over a full-screen app are retried on
+/// the next check rather than dropped.
+pub async fn maybe_auto_show(app: &tauri::AppHandle, data: &UsageData) {
+    let prefs = settings::get(app);
+    if !prefs.auto_show.enabled {
+        return;
+    }
+
+    let fresh: Vec<(String, String)> = {
+        let shown = AUTO_SHOWN.lock().unwrap();
+        critical_conditions(data, Utc::now())
+            .into_iter()
+            .filter(|(key, _)| !shown.contains(key))
+            .collect()
+    };
+    if fresh.is_empty() {
+        return;
+    }
+
+    if prefs.is_quiet_now() {
+        crate::log("notifier: critical condition during quiet hours, not showing panel");
+        return;
+    }
+    if prefs.auto_show.skip_when_fullscreen
+        && tokio::task::spawn_blocking(fullscreen::foreground_is_fullscreen)
+            .await
+            .unwrap_or(false)
+    {
+        crate::log("notifier: full-screen app in front, deferring auto-show");
+        return;
+    }
+
+    {
+        let mut shown = AUTO_SHOWN.lock().unwrap();
+        for (key, reason) in &fresh {
+            crate::log(&format!("notifier: auto-showing panel: {}", reason));
+            shown.push(key.clone());
         }
+        let excess = shown.len().saturating_sub(50);
+        shown.drain(..excess);
+    }
 
-        // Mark this window as notified
-        let mut guard = LAST_NOTIFIED_RESET.lock().unwrap();
-        *guard = Some(reset_at_str);
+    if !crate::PANEL_VISIBLE.load(Ordering::SeqCst) {
+        crate::show_panel(app, None);
     }
 }
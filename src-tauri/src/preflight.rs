@@ -0,0 +1,184 @@
+use crate::alerts::{self, Alert};
+use crate::commands::{self, CostsResult, UsageResult};
+use crate::log;
+use crate::settings::{self, PreflightSettings};
+use crate::templates;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use claudit_core::ccusage::CostCache;
+use claudit_core::token;
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::Manager;
+
+/// A check missed by more than this (asleep, or Claudit started later) is
+/// skipped for the day rather than run in the middle of work.
+const GRACE_MINUTES: i64 = 60;
+
+/// Local date the scheduled check last ran.
+static LAST_RUN: Mutex<Option<NaiveDate>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightReport {
+    pub ran_at: i64,
+    /// Everything that needs attention; empty when all is well.
+    pub problems: Vec<String>,
+}
+
+/// Whether the check scheduled at `at` should run at local time `now`.
+pub fn is_due(at: NaiveTime, now: NaiveDateTime, last_run: Option<NaiveDate>) -> bool {
+    if last_run == Some(now.date()) || now.time() < at {
+        return false;
+    }
+    (now.time() - at).num_minutes() < GRACE_MINUTES
+}
+
+/// Problems found by the token check (`sign_in`, its error if it failed) and
+/// in a freshly refreshed usage and costs result.
+pub fn problems(
+    sign_in: Option<&str>,
+    usage: &UsageResult,
+    costs: &CostsResult,
+    settings: &PreflightSettings,
+) -> Vec<String> {
+    let mut found = Vec::new();
+    match (sign_in, &usage.usage_error) {
+        (Some(e), _) => found.push(format!("Sign-in: {}", e)),
+        // Without a token the usage request fails too, for the same reason
+        (None, Some(e)) => found.push(format!("Usage: {}", e)),
+        (None, None) => {}
+    }
+    if let Some(ref data) = usage.usage {
+        for limit in data.limits.iter().filter(|l| l.usage_pct >= settings.warn_above_pct) {
+            found.push(format!("{} is at {:.0}%", limit.label, limit.usage_pct * 100.0));
+        }
+    }
+    if let Some(ref e) = costs.costs_error {
+        found.push(format!("ccusage: {}", e));
+    }
+    found
+}
+
+/// Refresh everything, check the token, and notify if anything needs attention.
+/// The scheduled check notifies at most once a day; a `manual` one always does.
+pub async fn run(app: &tauri::AppHandle, manual: bool) -> PreflightReport {
+    log("preflight: starting");
+    let prefs = settings::get(app).preflight.unwrap_or_default();
+
+    let sign_in = token::access_token().await.err().map(|e| e.to_string());
+    let usage = commands::usage_result(app).await;
+    let costs = commands::costs_result(app, app.state::<CostCache>().inner().clone()).await;
+    let found = problems(sign_in.as_deref(), &usage, &costs, &prefs);
+
+    let now = chrono::Local::now();
+    if found.is_empty() {
+        log("preflight: all clear");
+    } else {
+        log(&format!("preflight: {} problem(s)", found.len()));
+        let (title, body) = templates::render(
+            &templates::PREFLIGHT,
+            &settings::get(app).templates,
+            &[("count", found.len().to_string()), ("problems", found.join("\n"))],
+        );
+        let id = if manual {
+            format!("preflight:manual:{}", now.timestamp())
+        } else {
+            format!("preflight:{}", now.format("%Y-%m-%d"))
+        };
+        alerts::dispatch(
+            app,
+            Alert {
+                id,
+                kind: templates::PREFLIGHT.id.to_string(),
+                title,
+                body,
+            },
+        )
+        .await;
+    }
+    PreflightReport { ran_at: now.timestamp(), problems: found }
+}
+
+/// Run the scheduled check if it is due. Called once a minute.
+pub async fn tick(app: &tauri::AppHandle) {
+    let Some(prefs) = settings::get(app).preflight else { return };
+    let Some(at) = prefs.time() else { return };
+    let now = chrono::Local::now().naive_local();
+    {
+        let mut last = LAST_RUN.lock().unwrap();
+        if !is_due(at, now, *last) {
+            return;
+        }
+        *last = Some(now.date());
+    }
+    run(app, false).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claudit_core::usage_api::{UsageData, UsageLimit};
+
+    fn local(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn usage(session_pct: f64, error: Option<&str>) -> UsageResult {
+        UsageResult {
+            usage: Some(UsageData {
+                limits: vec![
+                    UsageLimit { label: "Current session".into(), usage_pct: session_pct, reset_at: None },
+                    UsageLimit { label: "Current week (all models)".into(), usage_pct: 0.2, reset_at: None },
+                ],
+                extra_usage: None,
+                plan: None,
+            }),
+            usage_error: error.map(str::to_string),
+            timestamp: "08:30:00".into(),
+            rate_limited: false,
+            stale: error.is_some(),
+            last_updated: None,
+            cooldown_until: None,
+            display: None,
+            generation: None,
+        }
+    }
+
+    fn costs(error: Option<&str>) -> CostsResult {
+        CostsResult {
+            costs: None,
+            costs_error: error.map(str::to_string),
+            formatted: None,
+            stale: false,
+            last_updated: None,
+            refreshing: false,
+            generation: None,
+        }
+    }
+
+    #[test]
+    fn check_is_due_once_a_day_within_the_grace_period() {
+        let at = NaiveTime::from_hms_opt(8, 30, 0).unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        assert!(!is_due(at, local("2026-03-02", "08:29"), None));
+        assert!(is_due(at, local("2026-03-02", "08:30"), None));
+        assert!(is_due(at, local("2026-03-02", "09:29"), today.pred_opt()));
+        // Already ran today, or missed by over an hour
+        assert!(!is_due(at, local("2026-03-02", "08:45"), Some(today)));
+        assert!(!is_due(at, local("2026-03-02", "09:30"), None));
+    }
+
+    #[test]
+    fn problems_cover_errors_and_limits_near_full() {
+        let settings = PreflightSettings::default();
+        assert!(problems(None, &usage(0.5, None), &costs(None), &settings).is_empty());
+        assert_eq!(
+            problems(None, &usage(0.95, Some("HTTP 500")), &costs(Some("not found")), &settings),
+            ["Usage: HTTP 500", "Current session is at 95%", "ccusage: not found"]
+        );
+        // A failed token check explains the usage error, so it is reported alone
+        assert_eq!(
+            problems(Some("expired"), &usage(0.5, Some("Unauthorized")), &costs(None), &settings),
+            ["Sign-in: expired"]
+        );
+    }
+}
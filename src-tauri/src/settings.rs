@@ -0,0 +1,634 @@
+use crate::dock::Anchor;
+use crate::{log, log_warn};
+use crate::logging::{self, Level};
+use crate::storage;
+use crate::templates::{self, TemplateText};
+use crate::tray;
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use claudit_core::history::{self, Retention};
+use claudit_core::http::{self, ProxyConfig};
+use claudit_core::keychain;
+use claudit_core::usage_api::{Backend, UsageSource};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// In-memory copy of the settings file, loaded on first access.
+static SETTINGS: Mutex<Option<Settings>> = Mutex::new(None);
+/// The cached settings differ from the file because the last write failed.
+static UNSAVED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Settings {
+    /// Named data sources; the Claude Code backend applies when none is active.
+    pub profiles: HashMap<String, Backend>,
+    pub active_profile: Option<String>,
+    pub notifications: NotificationSettings,
+    pub tray: TraySettings,
+    pub panel: PanelSettings,
+    /// Show the docked panel when the cursor rests in a screen corner; off when unset.
+    pub hot_corner: Option<HotCornerSettings>,
+    /// Proxy for all network calls; unset uses `HTTPS_PROXY` or the system proxy.
+    pub proxy: Option<ProxyConfig>,
+    pub auto_show: AutoShowSettings,
+    pub quiet_hours: Option<QuietHours>,
+    pub rules: RuleSettings,
+    pub format: FormatSettings,
+    pub history: HistorySettings,
+    /// Daily check before work hours; off when unset.
+    pub preflight: Option<PreflightSettings>,
+    pub updates: UpdateSettings,
+    pub logging: LoggingSettings,
+    /// Custom notification and report text, keyed by kind ("unused-tokens",
+    /// "burn-rate", "preflight", "update-available").
+    pub templates: HashMap<String, TemplateText>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Rule-based alerts (unused tokens, burn rate) on or off.
+    pub enabled: bool,
+    /// Unix time until which no notifications are sent, set by snoozing.
+    pub snoozed_until: Option<i64>,
+    pub desktop_enabled: bool,
+    pub slack_webhook_url: Option<String>,
+    pub webhook_url: Option<String>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            snoozed_until: None,
+            desktop_enabled: true,
+            slack_webhook_url: None,
+            webhook_url: None,
+        }
+    }
+}
+
+/// Which limit the tray icon ring visualizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TrayMetric {
+    #[default]
+    Session,
+    Weekly,
+}
+
+/// What the tray title next to the icon shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleMetric {
+    #[default]
+    Session,
+    Weekly,
+    Opus,
+    TodayCost,
+    /// Session and weekly together, e.g. "S:42% W:17%".
+    Combo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TraySettings {
+    pub icon_metric: TrayMetric,
+    pub title_metric: TitleMetric,
+    /// Custom title such as "S:{session}% O:{opus}%", used instead of
+    /// `title_metric`. Variables: session, week, sonnet and opus (whole
+    /// percents) and today (cost).
+    pub title_template: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct PanelSettings {
+    /// Where the docked panel opens.
+    pub anchor: Anchor,
+    /// The panel was detached when last used; it reopens detached at startup.
+    pub detached: bool,
+    /// Keep the detached panel above other windows.
+    pub stay_on_top: bool,
+    pub detached_geometry: Option<WindowGeometry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    /// Where maximized windows have their close button on Windows and Linux.
+    TopRight,
+    /// Away from window controls, the Apple menu and GNOME's Activities corner.
+    #[default]
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotCornerSettings {
+    pub corner: Corner,
+    /// How long the cursor has to stay in the corner, in milliseconds.
+    pub dwell_ms: u64,
+}
+
+impl Default for HotCornerSettings {
+    fn default() -> Self {
+        Self {
+            corner: Corner::BottomLeft,
+            dwell_ms: 400,
+        }
+    }
+}
+
+/// Background update checks every `check_interval_hours`. With `auto_install`,
+/// a new version is installed once the panel has been closed for a while and
+/// the app relaunches into it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateSettings {
+    pub auto_check: bool,
+    pub check_interval_hours: i64,
+    pub auto_install: bool,
+    /// When the last check ran, in Unix seconds.
+    pub last_check: Option<i64>,
+}
+
+impl Default for UpdateSettings {
+    fn default() -> Self {
+        Self {
+            auto_check: true,
+            check_interval_hours: 24,
+            auto_install: false,
+            last_check: None,
+        }
+    }
+}
+
+/// The debug log: lines below `level` are skipped, and the file is rotated at
+/// `max_size_kb`, keeping `keep_files` older ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingSettings {
+    pub level: Level,
+    pub max_size_kb: u64,
+    pub keep_files: u64,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: Level::Info,
+            max_size_kb: 1024,
+            keep_files: 3,
+        }
+    }
+}
+
+/// Outer position and inner size of the detached panel, in physical pixels.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Name of the monitor it was on, for the log.
+    pub monitor: Option<String>,
+}
+
+/// Pop the panel open by itself when a limit or the extra-usage budget runs out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoShowSettings {
+    pub enabled: bool,
+    pub skip_when_fullscreen: bool,
+}
+
+impl Default for AutoShowSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            skip_when_fullscreen: true,
+        }
+    }
+}
+
+/// How token counts are shown: "1.2M" or "1,234,567".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenUnits {
+    #[default]
+    Abbreviated,
+    Raw,
+}
+
+/// Number formatting for every display string the backend produces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatSettings {
+    /// Locale tag such as "en-GB" or "de-DE"; empty follows the system locale.
+    pub locale: String,
+    pub currency_symbol: String,
+    pub token_units: TokenUnits,
+}
+
+impl Default for FormatSettings {
+    fn default() -> Self {
+        Self {
+            locale: String::new(),
+            currency_symbol: "\u{a3}".to_string(),
+            token_units: TokenUnits::Abbreviated,
+        }
+    }
+}
+
+/// How long usage history is kept and how densely. Snapshots closer together
+/// than `min_snapshot_interval_secs` are merged, and after `compact_after_days`
+/// they are thinned to one per `compacted_interval_mins`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistorySettings {
+    pub retention_days: i64,
+    pub min_snapshot_interval_secs: i64,
+    pub compact_after_days: i64,
+    pub compacted_interval_mins: i64,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            retention_days: 14,
+            min_snapshot_interval_secs: 60,
+            compact_after_days: 2,
+            compacted_interval_mins: 15,
+        }
+    }
+}
+
+impl HistorySettings {
+    pub fn retention(&self) -> Retention {
+        Retention {
+            max_age_secs: self.retention_days * 24 * 3600,
+            min_interval_secs: self.min_snapshot_interval_secs,
+            compact_after_secs: self.compact_after_days * 24 * 3600,
+            compacted_interval_secs: self.compacted_interval_mins * 60,
+        }
+    }
+}
+
+/// Alert thresholds. The "use your tokens" alert fires when the session resets
+/// within `unused_min_minutes..=unused_max_minutes` and usage is below
+/// `unused_below_pct` (0.0 - 1.0). The burn-rate alert fires when the current
+/// pace hits the limit before the reset and within `burn_warn_minutes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSettings {
+    pub unused_min_minutes: i64,
+    pub unused_max_minutes: i64,
+    pub unused_below_pct: f64,
+    pub burn_warn_minutes: i64,
+}
+
+impl Default for RuleSettings {
+    fn default() -> Self {
+        Self {
+            unused_min_minutes: 30,
+            unused_max_minutes: 75,
+            unused_below_pct: 0.80,
+            burn_warn_minutes: 60,
+        }
+    }
+}
+
+/// Daily local-time window ("HH:MM" to "HH:MM") during which Claudit stays silent.
+/// A window whose end is before its start wraps past midnight; equal times
+/// make it empty, leaving only `weekends`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+    /// Also quiet all day on Saturday and Sunday.
+    #[serde(default)]
+    pub weekends: bool,
+}
+
+impl QuietHours {
+    fn parse(t: &str) -> Option<NaiveTime> {
+        NaiveTime::parse_from_str(t, "%H:%M").ok()
+    }
+
+    pub fn contains(&self, now: NaiveDateTime) -> bool {
+        if self.weekends && matches!(now.weekday(), Weekday::Sat | Weekday::Sun) {
+            return true;
+        }
+        let (Some(start), Some(end)) = (Self::parse(&self.start), Self::parse(&self.end)) else {
+            return false;
+        };
+        let now = now.time();
+        if start <= end {
+            now >= start && now < end
+        } else {
+            now >= start || now < end
+        }
+    }
+}
+
+/// A daily local-time ("HH:MM") check that refreshes everything and notifies
+/// only when something needs attention: sign-in, ccusage, or a limit at or
+/// above `warn_above_pct` (0.0 - 1.0).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreflightSettings {
+    pub time: String,
+    pub warn_above_pct: f64,
+}
+
+impl Default for PreflightSettings {
+    fn default() -> Self {
+        Self {
+            time: "08:30".to_string(),
+            warn_above_pct: 0.90,
+        }
+    }
+}
+
+impl PreflightSettings {
+    pub fn time(&self) -> Option<NaiveTime> {
+        QuietHours::parse(&self.time)
+    }
+}
+
+impl Settings {
+    /// Whether the current local time falls inside the configured quiet hours.
+    pub fn is_quiet_now(&self) -> bool {
+        self.is_quiet_at(chrono::Local::now().naive_local())
+    }
+
+    pub fn is_quiet_at(&self, now: NaiveDateTime) -> bool {
+        self.quiet_hours.as_ref().is_some_and(|q| q.contains(now))
+    }
+
+    /// Where usage and costs come from: the active profile's backend.
+    pub fn usage_source(&self) -> UsageSource {
+        self.active_profile
+            .as_ref()
+            .and_then(|name| {
+                self.profiles.get(name).map(|backend| UsageSource { profile: name.clone(), backend: backend.clone() })
+            })
+            .unwrap_or_default()
+    }
+
+    /// Why notifications are held back right now, if they are.
+    pub fn muted_reason(&self) -> Option<&'static str> {
+        let now = chrono::Local::now();
+        if self.notifications.snoozed_until.is_some_and(|until| now.timestamp() < until) {
+            Some("snoozed")
+        } else if self.is_quiet_at(now.naive_local()) {
+            Some("quiet hours")
+        } else {
+            None
+        }
+    }
+
+    /// Reject settings that would break at use time rather than at save time.
+    pub fn validate(&self) -> Result<(), String> {
+        let n = &self.notifications;
+        for (name, url) in [("Slack webhook", &n.slack_webhook_url), ("Webhook", &n.webhook_url)] {
+            if let Some(url) = url {
+                if !url.starts_with("https://") {
+                    return Err(format!("{} URL must start with https://", name));
+                }
+            }
+        }
+        if let Some(ref q) = self.quiet_hours {
+            for t in [&q.start, &q.end] {
+                if QuietHours::parse(t).is_none() {
+                    return Err(format!("Quiet hours time \"{}\" must be HH:MM", t));
+                }
+            }
+        }
+        let r = &self.rules;
+        if r.unused_min_minutes < 0 || r.unused_min_minutes > r.unused_max_minutes {
+            return Err("Unused-tokens window must satisfy 0 <= min <= max minutes".to_string());
+        }
+        if !(0.0..=1.0).contains(&r.unused_below_pct) {
+            return Err("Unused-tokens threshold must be between 0 and 1".to_string());
+        }
+        if r.burn_warn_minutes < 0 {
+            return Err("Burn-rate warning minutes must not be negative".to_string());
+        }
+        if let Some(ref p) = self.preflight {
+            if p.time().is_none() {
+                return Err(format!("Pre-flight time \"{}\" must be HH:MM", p.time));
+            }
+            if !(0.0..=1.0).contains(&p.warn_above_pct) {
+                return Err("Pre-flight threshold must be between 0 and 1".to_string());
+            }
+        }
+        if self.hot_corner.as_ref().is_some_and(|c| !(100..=5000).contains(&c.dwell_ms)) {
+            return Err("Hot corner dwell must be between 100 and 5000 ms".to_string());
+        }
+        if let Some(ref name) = self.active_profile {
+            if !self.profiles.contains_key(name) {
+                return Err(format!("No profile called \"{}\"", name));
+            }
+        }
+        for (name, backend) in &self.profiles {
+            if let Backend::AdminApi { ref budgets } = backend {
+                if budgets.monthly_spend.is_some_and(|s| s.is_nan() || s <= 0.0) {
+                    return Err(format!("Profile \"{}\": monthly spend budget must be positive", name));
+                }
+            }
+        }
+        if !(64..=100 * 1024).contains(&self.logging.max_size_kb) || self.logging.keep_files > 20 {
+            return Err("Log files must be 64 KB to 100 MB, keeping at most 20".to_string());
+        }
+        if !(1..=24 * 7).contains(&self.updates.check_interval_hours) {
+            return Err("Update check interval must be between 1 and 168 hours".to_string());
+        }
+        templates::validate(&self.templates)?;
+        if let Some(ref t) = self.tray.title_template {
+            templates::check(t, tray::TITLE_VARS).map_err(|e| format!("Tray title: {}", e))?;
+        }
+        let h = &self.history;
+        if !(1..=365).contains(&h.retention_days) {
+            return Err("History retention must be between 1 and 365 days".to_string());
+        }
+        if h.min_snapshot_interval_secs < 0 || h.compact_after_days < 0 {
+            return Err("History intervals must not be negative".to_string());
+        }
+        if h.compacted_interval_mins < 1 {
+            return Err("Compacted history interval must be at least one minute".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Current settings, read from disk the first time they are needed.
+pub fn get(app: &tauri::AppHandle) -> Settings {
+    let mut guard = SETTINGS.lock().unwrap();
+    if let Some(ref s) = *guard {
+        return s.clone();
+    }
+    let loaded = load(app);
+    apply(&loaded);
+    *guard = Some(loaded.clone());
+    loaded
+}
+
+/// The proxy password from the keychain, read once so saving settings
+/// doesn't go to the keychain every time.
+static PROXY_PASSWORD: Mutex<Option<Option<String>>> = Mutex::new(None);
+
+fn proxy_password() -> Option<String> {
+    PROXY_PASSWORD
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| keychain::proxy_password().ok())
+        .clone()
+}
+
+/// Store the proxy password in the keychain, or remove it with `None`, and
+/// start using it.
+pub fn set_proxy_password(app: &tauri::AppHandle, password: Option<&str>) -> Result<(), String> {
+    keychain::set_proxy_password(password).map_err(|e| e.to_string())?;
+    *PROXY_PASSWORD.lock().unwrap() = Some(password.map(str::to_string));
+    apply(&get(app));
+    Ok(())
+}
+
+/// Hand the settings the core library needs over to it.
+fn apply(settings: &Settings) {
+    let l = &settings.logging;
+    logging::configure(l.level, l.max_size_kb, l.keep_files);
+    history::set_retention(settings.history.retention());
+    let proxy = settings.proxy.clone().map(|mut p| {
+        if p.username.is_some() {
+            p.password = proxy_password();
+        }
+        p
+    });
+    if let Err(e) = http::set_proxy(proxy.as_ref()) {
+        log(&format!("settings: proxy not applied: {}", e));
+    }
+}
+
+fn load(app: &tauri::AppHandle) -> Settings {
+    let path = match storage::app_data_file(app, "settings.json") {
+        Some(p) => p,
+        None => return Settings::default(),
+    };
+    let mut settings = read(&path);
+    migrate_proxy_password(app, &mut settings);
+    settings
+}
+
+fn read(path: &Path) -> Settings {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log_warn(&format!("settings: parse error: {}", e));
+            Settings::default()
+        }),
+        Err(_) => Settings::default(),
+    }
+}
+
+/// The saved settings, applied, for a headless run without an app. Leaves
+/// the file as it is.
+pub fn load_headless() -> Settings {
+    let settings = read(&storage::headless_data_file("settings.json"));
+    apply(&settings);
+    settings
+}
+
+/// Older versions kept the proxy password in settings.json; move it to the
+/// keychain and rewrite the file without it.
+fn migrate_proxy_password(app: &tauri::AppHandle, settings: &mut Settings) {
+    let Some(password) = settings.proxy.as_mut().and_then(|p| p.password.take()) else {
+        return;
+    };
+    match keychain::set_proxy_password(Some(&password)) {
+        Ok(()) => {
+            log("settings: moved the proxy password to the keychain");
+            write(app, settings);
+        }
+        Err(e) => log_warn(&format!("settings: proxy password not moved to the keychain, re-enter it: {}", e)),
+    }
+    // Still used this session either way
+    *PROXY_PASSWORD.lock().unwrap() = Some(Some(password));
+}
+
+/// Validate, cache and persist new settings. If the file can't be written the
+/// settings still take effect; the write is retried in the background and
+/// reported through `get_health` until it succeeds.
+pub fn save(app: &tauri::AppHandle, settings: Settings) -> Result<Settings, String> {
+    settings.validate()?;
+    // Building a client is slow, so only a changed proxy is checked
+    if let Some(ref p) = settings.proxy {
+        if get(app).proxy.as_ref() != Some(p) {
+            http::validate_proxy(p)?;
+        }
+    }
+    apply(&settings);
+    *SETTINGS.lock().unwrap() = Some(settings.clone());
+    UNSAVED.store(true, Ordering::SeqCst);
+    if write(app, &settings) {
+        log("settings: saved");
+    }
+    Ok(settings)
+}
+
+/// Change part of the current settings and save them, for state the app
+/// records on its own (like window geometry) rather than via the UI.
+pub fn update(app: &tauri::AppHandle, change: impl FnOnce(&mut Settings)) {
+    let mut settings = get(app);
+    change(&mut settings);
+    if let Err(e) = save(app, settings) {
+        log(&format!("settings: update rejected: {}", e));
+    }
+}
+
+fn write(app: &tauri::AppHandle, settings: &Settings) -> bool {
+    let path = match storage::app_data_file(app, "settings.json") {
+        Some(p) => p,
+        None => return false,
+    };
+    let json = match serde_json::to_string_pretty(settings) {
+        Ok(j) => j,
+        Err(e) => {
+            log_warn(&format!("settings: serialize error: {}", e));
+            return false;
+        }
+    };
+    match storage::write_tracked("settings", &path, &json) {
+        Ok(()) => {
+            UNSAVED.store(false, Ordering::SeqCst);
+            true
+        }
+        Err(e) => {
+            log_warn(&format!("settings: write error, keeping in memory: {}", e));
+            false
+        }
+    }
+}
+
+pub fn has_unsaved() -> bool {
+    UNSAVED.load(Ordering::SeqCst)
+}
+
+/// Write cached settings that failed to save earlier. True if nothing is left unsaved.
+pub fn retry_unsaved(app: &tauri::AppHandle) -> bool {
+    if !has_unsaved() {
+        return true;
+    }
+    let cached = SETTINGS.lock().unwrap().clone();
+    match cached {
+        Some(s) => write(app, &s),
+        None => true,
+    }
+}
@@ -0,0 +1,234 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::format::NumberFormat;
+use crate::notifier;
+use crate::settings::{RuleSettings, Settings};
+use claudit_core::history::UsageSnapshot;
+use claudit_core::usage_api::{ExtraUsageInfo, UsageData, UsageLimit};
+
+/// Live checks run every 5 minutes, so that is the default replay resolution.
+const DEFAULT_STEP_MINUTES: i64 = 5;
+const DEFAULT_SESSION_HOURS: i64 = 5;
+/// Upper bound on evaluated samples, so a typo can't stall the backend.
+const MAX_STEPS: i64 = 20_000;
+
+/// A synthetic usage trajectory. Usage is interpolated linearly between
+/// `points`; the replay runs from minute 0 to the last point.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    /// RFC 3339 start time; defaults to now. Matters for quiet hours.
+    pub start: Option<String>,
+    pub step_minutes: Option<i64>,
+    /// Length of a session window; sessions reset every this many hours from `start`.
+    pub session_hours: Option<i64>,
+    pub points: Vec<ScenarioPoint>,
+    /// Thresholds to try instead of the saved ones.
+    pub rules: Option<RuleSettings>,
+}
+
+/// Usage at `minute` after the start, as fractions (0.0 - 1.0).
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScenarioPoint {
+    pub minute: i64,
+    pub session_pct: f64,
+    pub weekly_pct: Option<f64>,
+    pub extra_usage_pct: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulatedAlert {
+    pub minute: i64,
+    pub at: String,
+    pub id: String,
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+}
+
+fn interpolate(points: &[ScenarioPoint], minute: i64, value: impl Fn(&ScenarioPoint) -> Option<f64>) -> Option<f64> {
+    let known: Vec<(i64, f64)> = points.iter().filter_map(|p| value(p).map(|v| (p.minute, v))).collect();
+    let after = known.iter().position(|(m, _)| *m >= minute);
+    match after {
+        None => known.last().map(|(_, v)| *v),
+        Some(0) => known.first().map(|(_, v)| *v),
+        Some(i) => {
+            let (m0, v0) = known[i - 1];
+            let (m1, v1) = known[i];
+            Some(v0 + (v1 - v0) * (minute - m0) as f64 / (m1 - m0) as f64)
+        }
+    }
+}
+
+/// Replay `scenario` through the notification and auto-show rules with the
+/// given settings, returning each alert the first time it would fire.
+pub fn run(scenario: &Scenario, settings: &Settings) -> Result<Vec<SimulatedAlert>, String> {
+    let mut points = scenario.points.clone();
+    if points.is_empty() {
+        return Err("Scenario needs at least one point".to_string());
+    }
+    points.sort_by_key(|p| p.minute);
+    if points[0].minute < 0 {
+        return Err("Point minutes must not be negative".to_string());
+    }
+
+    let start = match scenario.start {
+        Some(ref s) => DateTime::parse_from_rfc3339(s)
+            .map_err(|e| format!("Invalid start time: {}", e))?
+            .with_timezone(&Utc),
+        None => Utc::now(),
+    };
+    let step = scenario.step_minutes.unwrap_or(DEFAULT_STEP_MINUTES);
+    let session_minutes = scenario.session_hours.unwrap_or(DEFAULT_SESSION_HOURS) * 60;
+    if step <= 0 || session_minutes <= 0 {
+        return Err("Step and session length must be positive".to_string());
+    }
+    let end = points[points.len() - 1].minute;
+    if end / step > MAX_STEPS {
+        return Err(format!("Scenario too long: more than {} steps", MAX_STEPS));
+    }
+
+    let rules = scenario.rules.as_ref().unwrap_or(&settings.rules);
+    let fmt = NumberFormat::new(&settings.format);
+    let weekly_reset = (start + Duration::days(7)).to_rfc3339();
+    let mut snapshots = Vec::new();
+    let mut seen = Vec::new();
+    let mut fired = Vec::new();
+
+    let mut minute = 0;
+    while minute <= end {
+        let now = start + Duration::minutes(minute);
+        let session_reset = start + Duration::minutes((minute / session_minutes + 1) * session_minutes);
+
+        let mut limits = vec![UsageLimit {
+            label: "Current session".to_string(),
+            usage_pct: interpolate(&points, minute, |p| Some(p.session_pct)).unwrap_or(0.0),
+            reset_at: Some(session_reset.to_rfc3339()),
+        }];
+        if let Some(pct) = interpolate(&points, minute, |p| p.weekly_pct) {
+            limits.push(UsageLimit {
+                label: "Current week (all models)".to_string(),
+                usage_pct: pct,
+                reset_at: Some(weekly_reset.clone()),
+            });
+        }
+        let data = UsageData {
+            limits,
+            extra_usage: interpolate(&points, minute, |p| p.extra_usage_pct).map(|pct| ExtraUsageInfo {
+                enabled: true,
+                monthly_limit: 0.0,
+                used_credits: 0.0,
+                utilization: pct,
+            }),
+            plan: None,
+        };
+
+        // Alerts and auto-show are deferred, not dropped, during quiet hours;
+        // mirror that here
+        let quiet = settings.is_quiet_at(now.with_timezone(&chrono::Local).naive_local());
+        let mut candidates = Vec::new();
+        if !quiet {
+            candidates.extend(
+                notifier::evaluate_rules(&data, &snapshots, now, rules, &fmt, &settings.templates)
+                    .into_iter()
+                    .map(|a| (a.id, a.kind, a.title, a.body)),
+            );
+        }
+        if settings.auto_show.enabled && !quiet {
+            for (key, reason) in notifier::critical_conditions(&data, now) {
+                candidates.push((key, "auto-show".to_string(), reason, "The panel would open".to_string()));
+            }
+        }
+
+        for (id, kind, title, body) in candidates {
+            if seen.contains(&id) {
+                continue;
+            }
+            seen.push(id.clone());
+            fired.push(SimulatedAlert { minute, at: now.to_rfc3339(), id, kind, title, body });
+        }
+        snapshots.push(UsageSnapshot {
+            timestamp: now.timestamp(),
+            buckets: data.limits.iter().map(|l| (l.label.clone(), l.usage_pct)).collect(),
+            projects: Default::default(),
+            models: Default::default(),
+        });
+        minute += step;
+    }
+
+    Ok(fired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::QuietHours;
+
+    fn scenario(points: &[(i64, f64)]) -> Scenario {
+        Scenario {
+            start: Some("2026-03-02T09:00:00Z".to_string()),
+            step_minutes: None,
+            session_hours: None,
+            points: points
+                .iter()
+                .map(|&(minute, session_pct)| ScenarioPoint {
+                    minute,
+                    session_pct,
+                    weekly_pct: None,
+                    extra_usage_pct: None,
+                })
+                .collect(),
+            rules: None,
+        }
+    }
+
+    #[test]
+    fn unused_tokens_fire_once_per_session_window() {
+        // Two five-hour windows at 10% usage
+        let alerts = run(&scenario(&[(0, 0.1), (600, 0.1)]), &Settings::default()).unwrap();
+        let unused: Vec<_> = alerts.iter().filter(|a| a.kind == "unused-tokens").collect();
+        assert_eq!(unused.iter().map(|a| a.minute).collect::<Vec<_>>(), [225, 525]);
+        assert_eq!(unused[0].at, "2026-03-02T12:45:00+00:00");
+    }
+
+    #[test]
+    fn scenario_rules_replace_the_saved_ones() {
+        let mut s = scenario(&[(0, 0.1), (300, 0.1)]);
+        s.rules = Some(RuleSettings { unused_below_pct: 0.05, ..RuleSettings::default() });
+        let alerts = run(&s, &Settings::default()).unwrap();
+        assert!(alerts.iter().all(|a| a.kind != "unused-tokens"));
+    }
+
+    #[test]
+    fn nothing_fires_in_quiet_hours() {
+        let settings = Settings {
+            quiet_hours: Some(QuietHours { start: "00:00".into(), end: "23:59".into(), weekends: true }),
+            ..Settings::default()
+        };
+        assert!(run(&scenario(&[(0, 0.1), (300, 0.95)]), &settings).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_scenarios_are_rejected() {
+        let settings = Settings::default();
+        assert!(run(&scenario(&[]), &settings).is_err());
+        assert!(run(&scenario(&[(-5, 0.1)]), &settings).is_err());
+        assert!(run(&scenario(&[(0, 0.1), (5 * (MAX_STEPS + 1), 0.1)]), &settings).is_err());
+
+        let mut s = scenario(&[(0, 0.1)]);
+        s.step_minutes = Some(0);
+        assert!(run(&s, &settings).is_err());
+        s.step_minutes = None;
+        s.start = Some("yesterday".into());
+        assert!(run(&s, &settings).is_err());
+    }
+
+    #[test]
+    fn usage_is_interpolated_between_points() {
+        let points = scenario(&[(0, 0.0), (100, 0.5), (200, 0.7)]).points;
+        assert_eq!(interpolate(&points, 50, |p| Some(p.session_pct)), Some(0.25));
+        assert_eq!(interpolate(&points, 150, |p| Some(p.session_pct)), Some(0.6));
+        assert_eq!(interpolate(&points, 500, |p| Some(p.session_pct)), Some(0.7));
+        assert_eq!(interpolate(&points, 50, |p| p.weekly_pct), None);
+    }
+}
@@ -0,0 +1,69 @@
+use crate::log_warn;
+use crate::settings;
+use claudit_core::usage_api::UsageSource;
+use std::fs;
+use std::path::PathBuf;
+
+pub use claudit_core::storage::write_tracked;
+
+/// `name` for data that belongs to `source`. Unchanged for the default
+/// source, so files from before profiles keep working; otherwise the profile
+/// goes before the extension, as in "usage_history.work.json".
+pub fn profile_file_name(name: &str, source: &UsageSource) -> String {
+    if *source == UsageSource::default() {
+        return name.to_string();
+    }
+    let profile: String = source
+        .profile
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.{}.{}", stem, profile, ext),
+        None => format!("{}.{}", name, profile),
+    }
+}
+
+/// [`app_data_file`] for the active profile's copy of `name`.
+pub fn profile_data_file(app: &tauri::AppHandle, name: &str) -> Option<PathBuf> {
+    app_data_file(app, &profile_file_name(name, &settings::get(app).usage_source()))
+}
+
+/// Path of `name` inside the app data dir when there is no app to ask, as
+/// for the headless CLI. The same dir Tauri resolves for the bundle identifier.
+pub fn headless_data_file(name: &str) -> PathBuf {
+    crate::logging::log_dir().join(name)
+}
+
+/// Path of `name` inside the app data dir, creating the dir if needed.
+pub fn app_data_file(app: &tauri::AppHandle, name: &str) -> Option<PathBuf> {
+    use tauri::Manager;
+    match app.path().app_data_dir() {
+        Ok(dir) => {
+            if !dir.exists() {
+                let _ = fs::create_dir_all(&dir);
+            }
+            Some(dir.join(name))
+        }
+        Err(e) => {
+            log_warn(&format!("storage: failed to get app data dir: {}", e));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claudit_core::usage_api::{AdminBudgets, Backend};
+
+    #[test]
+    fn profiles_other_than_the_default_get_their_own_files() {
+        assert_eq!(profile_file_name("usage_history.json", &UsageSource::default()), "usage_history.json");
+        let work = UsageSource { profile: "Work/EU".to_string(), backend: Backend::AdminApi { budgets: AdminBudgets::default() } };
+        assert_eq!(profile_file_name("usage_history.json", &work), "usage_history.Work_EU.json");
+        // A profile called "default" that isn't the default source is kept apart too
+        let admin = UsageSource { profile: "default".to_string(), ..work };
+        assert_eq!(profile_file_name("last_usage.json", &admin), "last_usage.default.json");
+    }
+}
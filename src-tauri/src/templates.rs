@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// User text for one kind of notification or report. Unset parts keep the
+/// built-in text. `{name}` is replaced by a variable; `{{` and `}}` are
+/// literal braces.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateText {
+    pub title: Option<String>,
+    pub body: Option<String>,
+}
+
+/// A templated message, identified by the alert kind it produces.
+pub struct Kind {
+    pub id: &'static str,
+    title: &'static str,
+    body: &'static str,
+    vars: &'static [&'static str],
+}
+
+pub const UNUSED_TOKENS: Kind = Kind {
+    id: "unused-tokens",
+    title: "Use your tokens!",
+    body: "You've only used {used_pct} of your session. ~{minutes_left}min left before it resets.",
+    vars: &["used_pct", "minutes_left"],
+};
+
+pub const BURN_RATE: Kind = Kind {
+    id: "burn-rate",
+    title: "Slow down",
+    body: "At this pace you'll hit the session limit in ~{minutes_to_limit} minutes, {minutes_early} minutes before it resets.",
+    vars: &["minutes_to_limit", "minutes_early"],
+};
+
+pub const PREFLIGHT: Kind = Kind {
+    id: "preflight",
+    title: "Claudit pre-flight: {count} issue(s)",
+    body: "{problems}",
+    vars: &["count", "problems"],
+};
+
+pub const UPDATE_AVAILABLE: Kind = Kind {
+    id: "update-available",
+    title: "Claudit {version} is available",
+    body: "You have {current}. Open the tray menu to install it.",
+    vars: &["version", "current"],
+};
+
+const KINDS: &[&Kind] = &[&UNUSED_TOKENS, &BURN_RATE, &PREFLIGHT, &UPDATE_AVAILABLE];
+
+/// Substitute `vars` into `template`. Fails on unknown variables and
+/// unmatched braces.
+pub fn fill(template: &str, vars: &[(&str, String)]) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err("unmatched {".to_string()),
+                    }
+                }
+                match vars.iter().find(|(n, _)| *n == name) {
+                    Some((_, value)) => out.push_str(value),
+                    None => return Err(format!("unknown placeholder {{{}}}", name)),
+                }
+            }
+            '}' => return Err("unmatched }".to_string()),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+/// Title and body for `kind`, from the user's template if set.
+pub fn render(kind: &Kind, templates: &HashMap<String, TemplateText>, vars: &[(&str, String)]) -> (String, String) {
+    let custom = templates.get(kind.id);
+    let part = |custom: Option<&String>, default: &str| {
+        custom
+            .and_then(|t| fill(t, vars).ok())
+            .unwrap_or_else(|| fill(default, vars).unwrap_or_else(|_| default.to_string()))
+    };
+    (
+        part(custom.and_then(|c| c.title.as_ref()), kind.title),
+        part(custom.and_then(|c| c.body.as_ref()), kind.body),
+    )
+}
+
+/// Check that `template` only uses `vars` and has matched braces.
+pub fn check(template: &str, vars: &[&str]) -> Result<(), String> {
+    // Every variable as a dummy value, so only structure is checked
+    let dummies: Vec<(&str, String)> = vars.iter().map(|&v| (v, String::new())).collect();
+    fill(template, &dummies)
+        .map(|_| ())
+        .map_err(|e| format!("{} (available: {})", e, vars.join(", ")))
+}
+
+/// Reject templates for unknown kinds or with placeholders their kind lacks.
+pub fn validate(templates: &HashMap<String, TemplateText>) -> Result<(), String> {
+    for (id, text) in templates {
+        let kind = KINDS
+            .iter()
+            .find(|k| k.id == id)
+            .ok_or_else(|| format!("No notification or report called \"{}\"", id))?;
+        for t in [&text.title, &text.body].into_iter().flatten() {
+            check(t, kind.vars).map_err(|e| format!("Template for \"{}\": {}", id, e))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_substitutes_and_escapes() {
+        let vars = [("used_pct", "40%".to_string()), ("minutes_left", "25".to_string())];
+        assert_eq!(fill("{used_pct} used, {minutes_left}min left", &vars).unwrap(), "40% used, 25min left");
+        assert_eq!(fill("{{used_pct}} is {used_pct}", &vars).unwrap(), "{used_pct} is 40%");
+        assert_eq!(fill("}}{{", &vars).unwrap(), "}{");
+
+        assert_eq!(fill("{nope}", &vars).unwrap_err(), "unknown placeholder {nope}");
+        assert_eq!(fill("left {minutes_left", &vars).unwrap_err(), "unmatched {");
+        assert_eq!(fill("left }", &vars).unwrap_err(), "unmatched }");
+    }
+
+    #[test]
+    fn validate_checks_kinds_and_placeholders() {
+        let text = |body: &str| TemplateText { title: None, body: Some(body.to_string()) };
+        let mut templates = HashMap::from([("update-available".to_string(), text("{version} over {current}"))]);
+        assert!(validate(&templates).is_ok());
+
+        templates.insert("burn-rate".to_string(), text("{used_pct}"));
+        let err = validate(&templates).unwrap_err();
+        assert!(err.starts_with("Template for \"burn-rate\": unknown placeholder {used_pct}"), "{}", err);
+        assert!(err.contains("available: minutes_to_limit, minutes_early"), "{}", err);
+
+        templates.insert("burn-rate".to_string(), text("{minutes_early"));
+        assert!(validate(&templates).unwrap_err().contains("unmatched {"));
+
+        let unknown = HashMap::from([("daily-digest".to_string(), text("hi"))]);
+        assert_eq!(validate(&unknown).unwrap_err(), "No notification or report called \"daily-digest\"");
+    }
+
+    #[test]
+    fn render_falls_back_to_the_built_in_text() {
+        let vars = [("version", "1.2.0".to_string()), ("current", "1.1.0".to_string())];
+        let broken = TemplateText { title: Some("{oops}".to_string()), body: Some("Now {current}".to_string()) };
+        let templates = HashMap::from([("update-available".to_string(), broken)]);
+        let (title, body) = render(&UPDATE_AVAILABLE, &templates, &vars);
+        assert_eq!(title, "Claudit 1.2.0 is available");
+        assert_eq!(body, "Now 1.1.0");
+    }
+}
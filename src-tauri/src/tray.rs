@@ -0,0 +1,338 @@
+use crate::format;
+use crate::{log, log_debug, log_warn};
+use crate::settings::{self, TitleMetric, TrayMetric, TraySettings};
+use crate::templates;
+use claudit_core::ccusage::CostData;
+use claudit_core::usage_api::UsageData;
+use std::sync::Mutex;
+use tauri::menu::{MenuItem, MenuItemBuilder};
+use tauri::Manager;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+pub const TRAY_ID: &str = "main-tray";
+
+/// Rendered at menu-bar @2x size; the OS scales it down elsewhere.
+const ICON_SIZE: u32 = 44;
+/// Samples per pixel along each axis, for anti-aliased edges.
+const SUPERSAMPLE: u32 = 4;
+const TRACK_RGBA: [u8; 4] = [128, 128, 128, 90];
+
+const GREEN: [u8; 3] = [0x34, 0xc7, 0x59];
+const YELLOW: [u8; 3] = [0xff, 0xcc, 0x00];
+const RED: [u8; 3] = [0xff, 0x3b, 0x30];
+/// Ring colour while requests are paused by a rate-limit cooldown.
+const GREY: [u8; 3] = [0x8e, 0x8e, 0x93];
+
+fn lerp(a: [u8; 3], b: [u8; 3], t: f64) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    let mix = |x: u8, y: u8| (x as f64 + (y as f64 - x as f64) * t).round() as u8;
+    [mix(a[0], b[0]), mix(a[1], b[1]), mix(a[2], b[2])]
+}
+
+/// Ring colour for a usage fraction: green up to 50%, fading through yellow at
+/// 70% to red at 90%, matching the panel's colour thresholds.
+pub fn usage_color(pct: f64) -> [u8; 3] {
+    if pct < 0.5 {
+        GREEN
+    } else if pct < 0.7 {
+        lerp(GREEN, YELLOW, (pct - 0.5) / 0.2)
+    } else {
+        lerp(YELLOW, RED, (pct - 0.7) / 0.2)
+    }
+}
+
+/// Draw a progress ring filled clockwise from 12 o'clock, as RGBA pixels.
+pub fn render_ring(pct: f64, size: u32) -> Vec<u8> {
+    render_ring_in(pct, size, usage_color(pct.clamp(0.0, 1.0)))
+}
+
+fn render_ring_in(pct: f64, size: u32, [r, g, b]: [u8; 3]) -> Vec<u8> {
+    let pct = pct.clamp(0.0, 1.0);
+    let center = size as f64 / 2.0;
+    let outer = center - 1.0;
+    let inner = outer * 0.62;
+    let samples = (SUPERSAMPLE * SUPERSAMPLE) as f64;
+
+    let mut rgba = vec![0u8; (size * size * 4) as usize];
+    for py in 0..size {
+        for px in 0..size {
+            let (mut fill, mut track) = (0.0, 0.0);
+            for sy in 0..SUPERSAMPLE {
+                for sx in 0..SUPERSAMPLE {
+                    let x = px as f64 + (sx as f64 + 0.5) / SUPERSAMPLE as f64 - center;
+                    let y = py as f64 + (sy as f64 + 0.5) / SUPERSAMPLE as f64 - center;
+                    let dist = (x * x + y * y).sqrt();
+                    if dist < inner || dist > outer {
+                        continue;
+                    }
+                    let angle = x.atan2(-y).rem_euclid(std::f64::consts::TAU);
+                    if angle / std::f64::consts::TAU < pct {
+                        fill += 1.0;
+                    } else {
+                        track += 1.0;
+                    }
+                }
+            }
+            let fill_a = fill / samples;
+            let track_a = track / samples * TRACK_RGBA[3] as f64 / 255.0;
+            let alpha = fill_a + track_a;
+            if alpha <= 0.0 {
+                continue;
+            }
+            let blend = |c: u8, t: u8| ((c as f64 * fill_a + t as f64 * track_a) / alpha).round() as u8;
+            let i = ((py * size + px) * 4) as usize;
+            rgba[i] = blend(r, TRACK_RGBA[0]);
+            rgba[i + 1] = blend(g, TRACK_RGBA[1]);
+            rgba[i + 2] = blend(b, TRACK_RGBA[2]);
+            rgba[i + 3] = (alpha * 255.0).round() as u8;
+        }
+    }
+    rgba
+}
+
+fn ring_label(app: &tauri::AppHandle) -> &'static str {
+    match settings::get(app).tray.icon_metric {
+        TrayMetric::Session => "Current session",
+        TrayMetric::Weekly => "Current week (all models)",
+    }
+}
+
+/// Variables a tray title template can use.
+pub const TITLE_VARS: &[&str] = &["session", "week", "sonnet", "opus", "today"];
+
+/// Usage and today's cost last shown, so either refresh can redraw the title.
+static LAST_TITLE_INPUTS: Mutex<(Option<UsageData>, Option<f64>)> = Mutex::new((None, None));
+
+fn builtin_title(metric: TitleMetric) -> &'static str {
+    match metric {
+        TitleMetric::Session => "{session}%",
+        TitleMetric::Weekly => "{week}%",
+        TitleMetric::Opus => "{opus}%",
+        TitleMetric::TodayCost => "{today}",
+        TitleMetric::Combo => "S:{session}% W:{week}%",
+    }
+}
+
+/// Tray title for `usage` and today's cost. Values not known yet show as "-".
+pub fn title(prefs: &TraySettings, usage: &UsageData, today: Option<f64>, fmt: &format::NumberFormat) -> String {
+    let pct = |label: &str| {
+        usage
+            .limits
+            .iter()
+            .find(|l| l.label == label)
+            .map_or("-".to_string(), |l| ((l.usage_pct * 100.0).floor() as i64).to_string())
+    };
+    let vars = [
+        ("session", pct("Current session")),
+        ("week", pct("Current week (all models)")),
+        ("sonnet", pct("Current week (Sonnet only)")),
+        ("opus", pct("Current week (Opus only)")),
+        ("today", today.map_or("-".to_string(), |c| fmt.currency(c))),
+    ];
+    let builtin = builtin_title(prefs.title_metric);
+    prefs
+        .title_template
+        .as_deref()
+        .and_then(|t| templates::fill(t, &vars).ok())
+        .unwrap_or_else(|| templates::fill(builtin, &vars).unwrap_or_else(|_| builtin.to_string()))
+}
+
+/// Refresh the tray icon ring, title and menu summary from freshly fetched usage.
+pub fn update(app: &tauri::AppHandle, usage: &UsageData) {
+    update_summary(app, usage);
+    let tray = match app.tray_by_id(TRAY_ID) {
+        Some(t) => t,
+        None => {
+            log_warn("tray not found by id main-tray");
+            return;
+        }
+    };
+
+    let fmt = format::for_app(app);
+    let today = {
+        let mut last = LAST_TITLE_INPUTS.lock().unwrap();
+        last.0 = Some(usage.clone());
+        last.1
+    };
+    let title = title(&settings::get(app).tray, usage, today, &fmt);
+    log_debug(&format!("set tray title: {}", title));
+    let _ = tray.set_title(Some(&title));
+
+    if let Some(limit) = usage.limits.iter().find(|l| l.label == ring_label(app)) {
+        let pixels = render_ring(limit.usage_pct, ICON_SIZE);
+        let icon = tauri::image::Image::new_owned(pixels, ICON_SIZE, ICON_SIZE);
+        // Coloured icons must not be treated as macOS template images
+        let _ = tray.set_icon_as_template(false);
+        let _ = tray.set_icon(Some(icon));
+        let tooltip = format!("Claudit - {}: {}", limit.label, fmt.percent(limit.usage_pct));
+        let _ = tray.set_tooltip(Some(&tooltip));
+    }
+}
+
+/// Show that usage requests are paused by a rate-limit cooldown until `until`
+/// (Unix seconds): a grey ring at the last known usage and the retry time as
+/// the title, instead of a percentage that is no longer being refreshed.
+pub fn show_cooldown(app: &tauri::AppHandle, until: i64, last: Option<&UsageData>) {
+    let tray = match app.tray_by_id(TRAY_ID) {
+        Some(t) => t,
+        None => {
+            log_warn("tray not found by id main-tray");
+            return;
+        }
+    };
+    let Some(retry_at) = format::local_time(until) else { return };
+    log(&format!("set tray cooldown until {}", retry_at));
+    let _ = tray.set_title(Some(format!("\u{23f8} {}", retry_at)));
+
+    let label = ring_label(app);
+    let pct = last
+        .and_then(|u| u.limits.iter().find(|l| l.label == label))
+        .map_or(0.0, |l| l.usage_pct);
+    let icon = tauri::image::Image::new_owned(render_ring_in(pct, ICON_SIZE, GREY), ICON_SIZE, ICON_SIZE);
+    let _ = tray.set_icon_as_template(false);
+    let _ = tray.set_icon(Some(icon));
+    let _ = tray.set_tooltip(Some(format!("Claudit - rate limited, retrying at {}", retry_at)));
+}
+
+/// Disabled info lines at the top of the tray menu, kept current by every
+/// usage and cost refresh.
+pub struct SummaryItems {
+    session: MenuItem<tauri::Wry>,
+    week: MenuItem<tauri::Wry>,
+    opus: MenuItem<tauri::Wry>,
+    today: MenuItem<tauri::Wry>,
+}
+
+impl SummaryItems {
+    pub fn build(app: &tauri::App) -> tauri::Result<Self> {
+        let info = |id: &str, text: &str| MenuItemBuilder::with_id(id, text).enabled(false).build(app);
+        Ok(Self {
+            session: info("summary-session", "Session: -")?,
+            week: info("summary-week", "Week: -")?,
+            opus: info("summary-opus", "Week (Opus): -")?,
+            today: info("summary-today", "Today: -")?,
+        })
+    }
+
+    pub fn items(&self) -> [&MenuItem<tauri::Wry>; 4] {
+        [&self.session, &self.week, &self.opus, &self.today]
+    }
+}
+
+/// Menu lines for `usage`: session (with its reset time), week, Opus week.
+pub fn usage_lines(usage: &UsageData, fmt: &format::NumberFormat) -> [String; 3] {
+    let find = |label: &str| usage.limits.iter().find(|l| l.label == label);
+    let pct = |label: &str| find(label).map_or("-".to_string(), |l| fmt.percent(l.usage_pct));
+    let resets = find("Current session")
+        .and_then(|l| l.reset_at.as_deref())
+        .and_then(|r| chrono::DateTime::parse_from_rfc3339(r).ok())
+        .and_then(|r| format::local_time(r.timestamp()))
+        .map_or(String::new(), |t| format!(" (resets {})", t));
+    [
+        format!("Session: {}{}", pct("Current session"), resets),
+        format!("Week: {}", pct("Current week (all models)")),
+        format!("Week (Opus): {}", pct("Current week (Opus only)")),
+    ]
+}
+
+fn update_summary(app: &tauri::AppHandle, usage: &UsageData) {
+    let Some(items) = app.try_state::<SummaryItems>() else { return };
+    let [session, week, opus] = usage_lines(usage, &format::for_app(app));
+    let _ = items.session.set_text(session);
+    let _ = items.week.set_text(week);
+    let _ = items.opus.set_text(opus);
+}
+
+/// Show today's cost in the tray menu.
+pub fn update_costs(app: &tauri::AppHandle, costs: &CostData) {
+    let fmt = format::for_app(app);
+    let usage = {
+        let mut last = LAST_TITLE_INPUTS.lock().unwrap();
+        last.1 = Some(costs.today);
+        last.0.clone()
+    };
+    // The title shows the retry time during a cooldown; leave it there
+    let cooling_down = claudit_core::usage_api::cooldown_until().is_some();
+    if let (Some(usage), Some(tray), false) = (usage, app.tray_by_id(TRAY_ID), cooling_down) {
+        let _ = tray.set_title(Some(title(&settings::get(app).tray, &usage, Some(costs.today), &fmt)));
+    }
+    let Some(items) = app.try_state::<SummaryItems>() else { return };
+    let _ = items.today.set_text(format!("Today: {}", fmt.currency(costs.today)));
+}
+
+/// Put the tray menu's summary lines on the clipboard.
+pub fn copy_summary(app: &tauri::AppHandle) {
+    let Some(items) = app.try_state::<SummaryItems>() else { return };
+    let text = items.items().iter().filter_map(|i| i.text().ok()).collect::<Vec<_>>().join("\n");
+    if let Err(e) = app.clipboard().write_text(text) {
+        log_warn(&format!("tray: copy summary failed: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::FormatSettings;
+    use claudit_core::usage_api::UsageLimit;
+
+    fn pixel(rgba: &[u8], x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * ICON_SIZE + x) * 4) as usize;
+        [rgba[i], rgba[i + 1], rgba[i + 2], rgba[i + 3]]
+    }
+
+    #[test]
+    fn ring_fills_clockwise_from_the_top() {
+        let rgba = render_ring(0.25, ICON_SIZE);
+        assert_eq!(rgba.len(), (ICON_SIZE * ICON_SIZE * 4) as usize);
+        // Half past one on the ring is filled, nine o'clock is track, the hole is empty
+        assert_eq!(pixel(&rgba, 34, 10), [GREEN[0], GREEN[1], GREEN[2], 255]);
+        assert_eq!(pixel(&rgba, 5, 22), TRACK_RGBA);
+        assert_eq!(pixel(&rgba, 22, 22), [0, 0, 0, 0]);
+
+        let grey = render_ring_in(0.25, ICON_SIZE, GREY);
+        assert_eq!(pixel(&grey, 34, 10), [GREY[0], GREY[1], GREY[2], 255]);
+    }
+
+    #[test]
+    fn ring_colour_runs_from_green_to_red() {
+        assert_eq!(usage_color(0.0), GREEN);
+        assert_eq!(usage_color(1.0), RED);
+        assert_eq!(pixel(&render_ring(0.0, ICON_SIZE), 34, 10), TRACK_RGBA);
+        assert_eq!(pixel(&render_ring(1.0, ICON_SIZE), 5, 22), [RED[0], RED[1], RED[2], 255]);
+    }
+
+    #[test]
+    fn titles_fill_each_template() {
+        let limit = |label: &str, usage_pct| UsageLimit { label: label.to_string(), usage_pct, reset_at: None };
+        let usage = UsageData {
+            limits: vec![
+                limit("Current session", 0.429),
+                limit("Current week (all models)", 0.17),
+                limit("Current week (Opus only)", 0.05),
+            ],
+            extra_usage: None,
+            plan: None,
+        };
+        let fmt = format::NumberFormat::new(&FormatSettings {
+            locale: "en-US".to_string(),
+            currency_symbol: "$".to_string(),
+            ..Default::default()
+        });
+        let title_for = |title_metric, title_template: Option<&str>, today| {
+            let prefs = TraySettings {
+                title_metric,
+                title_template: title_template.map(str::to_string),
+                ..Default::default()
+            };
+            title(&prefs, &usage, today, &fmt)
+        };
+
+        assert_eq!(title_for(TitleMetric::Session, None, None), "42%");
+        assert_eq!(title_for(TitleMetric::Weekly, None, None), "17%");
+        assert_eq!(title_for(TitleMetric::Opus, None, None), "5%");
+        assert_eq!(title_for(TitleMetric::TodayCost, None, Some(1.5)), "$1.50");
+        assert_eq!(title_for(TitleMetric::TodayCost, None, None), "-");
+        assert_eq!(title_for(TitleMetric::Combo, None, None), "S:42% W:17%");
+        assert_eq!(title_for(TitleMetric::Session, Some("{sonnet}% {today}"), Some(2.0)), "-% $2.00");
+    }
+}
@@ -0,0 +1,202 @@
+use crate::alerts::{self, Alert};
+use crate::events::{self, Event, UpdateProgress};
+use crate::{log, log_warn};
+use crate::settings;
+use crate::templates;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::menu::{MenuItem, MenuItemBuilder};
+use tauri::Manager;
+use tauri_plugin_updater::{Updater, UpdaterExt};
+
+/// How often the background task looks at whether a check is due.
+const POLL_SECS: u64 = 30 * 60;
+/// The panel has to stay closed this long before an automatic install.
+const IDLE_MINUTES: i64 = 10;
+
+/// Version found by the last check and not installed yet.
+static AVAILABLE: Mutex<Option<String>> = Mutex::new(None);
+
+/// An update the updater endpoint offers.
+#[derive(Debug, Clone)]
+pub struct Found {
+    pub version: String,
+    pub notes: Option<String>,
+}
+
+/// The tray menu entry that checks for, and then installs, updates.
+pub struct UpdateMenuItem(MenuItem<tauri::Wry>);
+
+impl UpdateMenuItem {
+    pub fn build(app: &tauri::App) -> tauri::Result<Self> {
+        MenuItemBuilder::with_id("update", "Check for Updates").build(app).map(Self)
+    }
+
+    pub fn item(&self) -> &MenuItem<tauri::Wry> {
+        &self.0
+    }
+}
+
+fn set_menu_text(app: &tauri::AppHandle, text: &str) {
+    if let Some(item) = app.try_state::<UpdateMenuItem>() {
+        let _ = item.0.set_text(text);
+    }
+}
+
+/// Whether a scheduled check is due, `interval_hours` after the last one.
+pub fn is_due(last_check: Option<i64>, interval_hours: i64, now: i64) -> bool {
+    last_check.is_none_or(|last| now - last >= interval_hours * 3600)
+}
+
+/// The updater, going through the configured proxy like every other request.
+fn updater(app: &tauri::AppHandle) -> Result<Updater, String> {
+    let mut builder = app.updater_builder();
+    if let Some(p) = claudit_core::http::proxy() {
+        let mut url = tauri::Url::parse(&p.url).map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        if let Some(ref user) = p.username {
+            let _ = url.set_username(user);
+            let _ = url.set_password(p.password.as_deref());
+        }
+        builder = builder.proxy(url);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Ask the updater endpoint for a newer version, remembering the answer for
+/// the tray menu and the schedule.
+pub async fn check(app: &tauri::AppHandle) -> Result<Option<Found>, String> {
+    let updater = updater(app)?;
+    let update = updater.check().await.map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp();
+    settings::update(app, |s| s.updates.last_check = Some(now));
+
+    let found = update.map(|u| Found { version: u.version.clone(), notes: u.body.clone() });
+    *AVAILABLE.lock().unwrap() = found.as_ref().map(|f| f.version.clone());
+    match found {
+        Some(ref f) => {
+            log(&format!("updates: v{} available", f.version));
+            set_menu_text(app, &format!("Install Update (v{})", f.version));
+        }
+        None => set_menu_text(app, "Check for Updates"),
+    }
+    Ok(found)
+}
+
+/// Download and install the latest update. The app has to be restarted to
+/// run it.
+pub async fn install(app: &tauri::AppHandle) -> Result<(), String> {
+    let updater = updater(app)?;
+    let update = updater.check().await.map_err(|e| e.to_string())?;
+    let update = update.ok_or_else(|| "No update available".to_string())?;
+    log(&format!("updates: downloading v{}", update.version));
+    events::emit(app, Event::UpdateProgress(UpdateProgress::Downloading));
+    set_menu_text(app, "Downloading Update...");
+
+    if let Err(e) = update.download_and_install(|_chunk, _total| {}, || {}).await {
+        set_menu_text(app, &format!("Install Update (v{})", update.version));
+        return Err(e.to_string());
+    }
+
+    log("updates: download and install complete");
+    *AVAILABLE.lock().unwrap() = None;
+    events::emit(app, Event::UpdateProgress(UpdateProgress::Done));
+    set_menu_text(app, "Restart to Update");
+    Ok(())
+}
+
+async fn notify(app: &tauri::AppHandle, found: &Found) {
+    let prefs = settings::get(app);
+    let (title, body) = templates::render(
+        &templates::UPDATE_AVAILABLE,
+        &prefs.templates,
+        &[
+            ("version", found.version.clone()),
+            ("current", env!("CARGO_PKG_VERSION").to_string()),
+        ],
+    );
+    // Keyed by version, so each release is announced once, and only on this
+    // machine: the Slack and webhook channels are for the team's usage
+    alerts::dispatch_desktop(
+        app,
+        Alert {
+            id: format!("update:{}", found.version),
+            kind: templates::UPDATE_AVAILABLE.id.into(),
+            title,
+            body,
+        },
+    )
+    .await;
+}
+
+/// Wait until the panel has been closed for a while, then install and
+/// relaunch. Gives up if automatic installs are turned off meanwhile.
+async fn install_when_idle(app: &tauri::AppHandle) {
+    let mut closed_for = 0;
+    while closed_for < IDLE_MINUTES {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        if !settings::get(app).updates.auto_install {
+            log("updates: automatic install turned off, not installing");
+            return;
+        }
+        closed_for = if crate::PANEL_VISIBLE.load(Ordering::SeqCst) { 0 } else { closed_for + 1 };
+    }
+    match install(app).await {
+        Ok(()) => {
+            log("updates: relaunching into the new version");
+            crate::logging::flush();
+            app.restart();
+        }
+        Err(e) => log_warn(&format!("updates: automatic install failed: {}", e)),
+    }
+}
+
+/// The tray menu entry was clicked: install a known update and relaunch, or
+/// check for one.
+pub fn menu_clicked(app: &tauri::AppHandle) {
+    let app = app.clone();
+    let available = AVAILABLE.lock().unwrap().clone();
+    tauri::async_runtime::spawn(async move {
+        if available.is_some() {
+            match install(&app).await {
+                Ok(()) => {
+                    crate::logging::flush();
+                    app.restart();
+                }
+                Err(e) => log_warn(&format!("updates: install failed: {}", e)),
+            }
+            return;
+        }
+        set_menu_text(&app, "Checking...");
+        match check(&app).await {
+            Ok(Some(_)) => {}
+            Ok(None) => set_menu_text(&app, "Claudit Is Up to Date"),
+            Err(e) => {
+                log_warn(&format!("updates: check failed: {}", e));
+                set_menu_text(&app, "Check for Updates");
+            }
+        }
+    });
+}
+
+/// Check for updates on the configured schedule, announcing each new version
+/// and installing it when automatic installs are on. Runs for the life of the app.
+pub async fn run(app: tauri::AppHandle) {
+    loop {
+        let prefs = settings::get(&app).updates;
+        if prefs.auto_check && is_due(prefs.last_check, prefs.check_interval_hours, chrono::Utc::now().timestamp()) {
+            log("updates: scheduled check");
+            match check(&app).await {
+                Ok(Some(found)) => {
+                    notify(&app, &found).await;
+                    if prefs.auto_install {
+                        install_when_idle(&app).await;
+                    }
+                }
+                Ok(None) => log("updates: up to date"),
+                Err(e) => log_warn(&format!("updates: check failed: {}", e)),
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(POLL_SECS)).await;
+    }
+}
@@ -0,0 +1,89 @@
+use crate::commands;
+use crate::events::{self, ActivityDetected, Event};
+use crate::{log, log_debug, log_warn};
+use claudit_core::ccusage::CostCache;
+use claudit_core::projects;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::Duration;
+use tauri::Manager;
+use tokio::sync::mpsc;
+
+/// Writes closer together than this are one burst of activity.
+const DEBOUNCE: Duration = Duration::from_secs(3);
+/// At most one ccusage run this often during a long session.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// How often to look again for a projects directory that doesn't exist yet.
+const WATCH_RETRY: Duration = Duration::from_secs(60);
+
+/// The project directory a write under `root` belongs to, if it is to a
+/// transcript.
+fn transcript_project(root: &Path, path: &Path) -> Option<String> {
+    if path.extension()? != "jsonl" {
+        return None;
+    }
+    let project = path.strip_prefix(root).ok()?.components().next()?;
+    Some(project.as_os_str().to_string_lossy().into_owned())
+}
+
+/// Watch Claude Code's transcripts and, when they are written to, drop the
+/// cached costs and refresh them. Usage stays on its normal schedule, since
+/// its API is rate limited. Runs for the life of the app.
+pub async fn run(app: tauri::AppHandle) {
+    let Some(root) = projects::projects_dir() else {
+        log("watcher: no home directory, not watching for activity");
+        return;
+    };
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let handler_root = root.clone();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else {
+            return;
+        };
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            return;
+        }
+        for path in &event.paths {
+            if let Some(project) = transcript_project(&handler_root, path) {
+                let _ = tx.send(project);
+            }
+        }
+    });
+    // Kept alive by this task; dropping it stops the events
+    let mut watcher = match watcher {
+        Ok(w) => w,
+        Err(e) => {
+            log_warn(&format!("watcher: could not start: {}", e));
+            return;
+        }
+    };
+    // Claude Code creates the directory on first use, which may be after launch
+    let mut logged = false;
+    while let Err(e) = watcher.watch(&root, RecursiveMode::Recursive) {
+        if !logged {
+            log_warn(&format!("watcher: could not watch {}, retrying: {}", root.display(), e));
+            logged = true;
+        }
+        tokio::time::sleep(WATCH_RETRY).await;
+    }
+    log(&format!("watcher: watching {}", root.display()));
+
+    while let Some(first) = rx.recv().await {
+        tokio::time::sleep(DEBOUNCE).await;
+        let mut touched = BTreeSet::from([first]);
+        while let Ok(project) = rx.try_recv() {
+            touched.insert(project);
+        }
+        let projects: Vec<String> = touched.into_iter().collect();
+        log_debug(&format!("watcher: activity in {}", projects.join(", ")));
+
+        let cost_cache = app.state::<CostCache>().inner().clone();
+        cost_cache.invalidate();
+        events::emit(&app, Event::ActivityDetected(ActivityDetected { projects }));
+        // Awaited, so only one ccusage run is in flight; writes meanwhile wait
+        // in the channel for the next refresh
+        commands::refresh_costs(&app, cost_cache).await;
+        tokio::time::sleep(MIN_REFRESH_INTERVAL).await;
+    }
+}
@@ -0,0 +1,167 @@
+// Contract tests for the event payloads the frontend and other consumers rely
+// on. A failure here means the wire format changed: bump
+// `events::SCHEMA_VERSION` and update these tests along with the consumers.
+
+use claudit_core::ccusage::CostData;
+use claudit_core::usage_api::{UsageData, UsageLimit};
+use claudit_lib::events::{self, ActivityDetected, CostsResult, Event, HistoryResult, UpdateProgress, UsageResult};
+use serde_json::{json, Value};
+
+fn keys(value: &Value) -> Vec<String> {
+    let mut keys: Vec<String> = value.as_object().expect("payload is an object").keys().cloned().collect();
+    keys.sort();
+    keys
+}
+
+fn sample_usage() -> UsageResult {
+    UsageResult {
+        usage: Some(UsageData {
+            limits: vec![UsageLimit {
+                label: "Current session".to_string(),
+                usage_pct: 0.42,
+                reset_at: Some("2026-01-05T15:00:00+00:00".to_string()),
+            }],
+            extra_usage: None,
+            plan: Some("Max (5x)".to_string()),
+        }),
+        usage_error: None,
+        timestamp: "2026-01-05T12:00:00+00:00".to_string(),
+        rate_limited: false,
+        stale: false,
+        last_updated: Some(1_767_614_400),
+        cooldown_until: None,
+        display: None,
+        generation: Some(2),
+    }
+}
+
+#[test]
+fn schema_lists_every_event_at_version_2() {
+    let schema = events::schema();
+    assert_eq!(schema.version, 2);
+    assert_eq!(
+        schema.events,
+        [
+            "usage-ready",
+            "costs-ready",
+            "history-ready",
+            "panel-shown",
+            "panel-hidden",
+            "panel-detached",
+            "panel-attached",
+            "snooze-changed",
+            "update-progress",
+            "activity-detected",
+        ]
+    );
+}
+
+#[test]
+fn usage_ready_payload_keeps_its_fields() {
+    let event = Event::UsageReady(sample_usage());
+    assert_eq!(event.name(), "usage-ready");
+    let payload = event.payload();
+    assert_eq!(
+        keys(&payload),
+        [
+            "cooldown_until",
+            "display",
+            "generation",
+            "last_updated",
+            "rate_limited",
+            "stale",
+            "timestamp",
+            "usage",
+            "usage_error",
+        ]
+    );
+    assert_eq!(payload["usage"]["limits"][0], json!({
+        "label": "Current session",
+        "usage_pct": 0.42,
+        "reset_at": "2026-01-05T15:00:00+00:00",
+    }));
+
+    let back: UsageResult = serde_json::from_value(payload).unwrap();
+    assert_eq!(back.last_updated, Some(1_767_614_400));
+}
+
+#[test]
+fn costs_and_history_payloads_keep_their_fields() {
+    let costs = CostsResult {
+        costs: Some(CostData { today: 1.5, week: 10.0, month: 40.0 }),
+        costs_error: None,
+        formatted: None,
+        stale: true,
+        last_updated: Some(1_767_614_400),
+        refreshing: true,
+        generation: Some(3),
+    };
+    let payload = Event::CostsReady(costs).payload();
+    assert_eq!(
+        keys(&payload),
+        ["costs", "costs_error", "formatted", "generation", "last_updated", "refreshing", "stale"]
+    );
+    assert_eq!(payload["costs"], json!({ "today": 1.5, "week": 10.0, "month": 40.0 }));
+
+    let history = Event::HistoryReady(HistoryResult { generation: Some(3) }).payload();
+    assert_eq!(history, json!({ "generation": 3 }));
+}
+
+#[test]
+fn simple_payloads_are_plain_values() {
+    for event in [Event::PanelShown, Event::PanelHidden, Event::PanelDetached, Event::PanelAttached] {
+        assert_eq!(event.payload(), Value::Null, "{}", event.name());
+    }
+    assert_eq!(Event::SnoozeChanged(Some(1_767_614_400)).payload(), json!(1_767_614_400));
+    assert_eq!(Event::SnoozeChanged(None).payload(), Value::Null);
+    assert_eq!(Event::UpdateProgress(UpdateProgress::Downloading).payload(), json!("downloading"));
+    assert_eq!(Event::UpdateProgress(UpdateProgress::Done).payload(), json!("done"));
+    let activity = Event::ActivityDetected(ActivityDetected { projects: vec!["-Users-me-work-app".to_string()] });
+    assert_eq!(activity.payload(), json!({ "projects": ["-Users-me-work-app"] }));
+}
+
+#[test]
+fn schema_describes_the_object_payloads() {
+    let schema = events::schema();
+    let costs = CostsResult {
+        costs: None,
+        costs_error: None,
+        formatted: None,
+        stale: false,
+        last_updated: None,
+        refreshing: false,
+        generation: None,
+    };
+    let payloads = [
+        Event::UsageReady(sample_usage()),
+        Event::CostsReady(costs),
+        Event::HistoryReady(HistoryResult { generation: None }),
+        Event::ActivityDetected(ActivityDetected { projects: Vec::new() }),
+    ];
+    assert_eq!(schema.fields.len(), payloads.len());
+    for event in payloads {
+        let mut fields = schema.fields[event.name()].clone();
+        fields.sort();
+        assert_eq!(keys(&event.payload()), fields, "{}", event.name());
+    }
+}
+
+#[test]
+fn usage_payload_without_optional_fields_parses() {
+    // Consumers may replay payloads that lack the fields marked `default`
+    let payload = json!({
+        "usage": {
+            "limits": [{ "label": "Current session", "usage_pct": 0.9, "reset_at": null }],
+            "extra_usage": null,
+            "plan": null
+        },
+        "usage_error": null,
+        "timestamp": "2026-01-05T12:00:00+00:00",
+        "rate_limited": true,
+        "stale": true
+    });
+    let parsed: UsageResult = serde_json::from_value(payload).unwrap();
+    assert!(parsed.rate_limited && parsed.stale);
+    assert_eq!(parsed.usage.unwrap().limits[0].usage_pct, 0.9);
+    assert!(parsed.cooldown_until.is_none());
+}
@@ -0,0 +1,465 @@
+// End-to-end tests of the data pipeline against a local mock of the Anthropic
+// API (wiremock) and a fake `ccusage` executable. Endpoints and credentials are
+// picked up from process env vars, so tests take `ENV_LOCK` to run one at a time.
+
+use claudit_core::ccusage::{self, CostCache, CCUSAGE_PATH_ENV_VAR};
+use claudit_core::history;
+use claudit_core::keychain::{ADMIN_KEY_ENV_VAR, CREDENTIALS_FILE_ENV_VAR, TOKEN_ENV_VAR};
+use claudit_core::usage_api::{self, AdminBudgets, Backend, UsageSource, API_BASE_ENV_VAR};
+use claudit_lib::{collect_usage, collect_usage_from, LastUsage, UsageResult};
+use serde_json::json;
+use std::path::PathBuf;
+use tempfile::TempDir;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+static ENV_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+struct Harness {
+    server: MockServer,
+    dir: TempDir,
+    /// Per test, so one test's good response isn't served in another.
+    last: LastUsage,
+}
+
+impl Harness {
+    /// Start a mock server and point the app at it with the given OAuth blob.
+    async fn start(access_token: &str, expires_at_ms: i64) -> Self {
+        let server = MockServer::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let harness = Harness { server, dir, last: LastUsage::new() };
+        harness.write_credentials(access_token, expires_at_ms);
+
+        usage_api::clear_cooldown();
+        std::env::remove_var(TOKEN_ENV_VAR);
+        std::env::set_var(API_BASE_ENV_VAR, harness.server.uri());
+        std::env::set_var(CREDENTIALS_FILE_ENV_VAR, harness.credentials_path());
+        // Keep transcripts (and anything else Claude Code keeps) inside the tempdir
+        std::env::set_var("CLAUDE_CONFIG_DIR", harness.dir.path());
+        harness
+    }
+
+    fn credentials_path(&self) -> PathBuf {
+        self.dir.path().join(".credentials.json")
+    }
+
+    fn history_path(&self) -> PathBuf {
+        self.dir.path().join("usage_history.json")
+    }
+
+    fn write_credentials(&self, access_token: &str, expires_at_ms: i64) {
+        let blob = json!({
+            "claudeAiOauth": {
+                "accessToken": access_token,
+                "refreshToken": "refresh-1",
+                "expiresAt": expires_at_ms,
+                "subscriptionType": "max",
+                "scopes": ["user:inference", "user:profile"],
+            }
+        });
+        let path = self.credentials_path();
+        std::fs::write(&path, blob.to_string()).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+    }
+
+    fn stored_oauth(&self) -> serde_json::Value {
+        let raw = std::fs::read_to_string(self.credentials_path()).unwrap();
+        serde_json::from_str::<serde_json::Value>(&raw).unwrap()["claudeAiOauth"].clone()
+    }
+}
+
+fn far_future_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis() + 24 * 3600 * 1000
+}
+
+fn usage_body(session_pct: f64) -> serde_json::Value {
+    json!({
+        "five_hour": { "utilization": session_pct, "resets_at": "2026-03-05T14:30:00+00:00" },
+        "seven_day": { "utilization": 20.0, "resets_at": "2026-03-09T08:00:00+00:00" },
+        "seven_day_opus": null,
+    })
+}
+
+fn session_pct(result: &UsageResult) -> Option<f64> {
+    result
+        .usage
+        .as_ref()?
+        .limits
+        .iter()
+        .find(|l| l.label == "Current session")
+        .map(|l| l.usage_pct)
+}
+
+#[tokio::test]
+async fn usage_is_fetched_and_written_to_history() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("token-1", far_future_ms()).await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .and(header("authorization", "Bearer token-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(42.0)))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+
+    let result = collect_usage(&h.last, Some(h.history_path()), None).await;
+    assert_eq!(result.usage_error, None);
+    assert_eq!(session_pct(&result), Some(0.42));
+    assert!(!result.rate_limited);
+
+    let stored = history::load_history_at(&h.history_path());
+    assert_eq!(stored.snapshots.len(), 1);
+    assert_eq!(stored.snapshots[0].buckets.get("Current session"), Some(&0.42));
+}
+
+#[tokio::test]
+async fn snapshots_attribute_usage_to_active_projects() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("token-1", far_future_ms()).await;
+    let now = chrono::Utc::now();
+
+    // An earlier snapshot at 10%, then transcripts from two projects since
+    let seeded = json!({ "snapshots": [
+        { "timestamp": now.timestamp() - 120, "buckets": { "Current session": 0.10 } }
+    ] });
+    std::fs::write(h.history_path(), seeded.to_string()).unwrap();
+    let transcripts = h.dir.path().join("projects").join("-work-app");
+    std::fs::create_dir_all(&transcripts).unwrap();
+    let line = |cwd: &str, tokens: u64| {
+        json!({
+            "timestamp": (now - chrono::Duration::seconds(30)).to_rfc3339(),
+            "cwd": cwd,
+            "message": { "usage": { "input_tokens": tokens, "output_tokens": 0, "cache_read_input_tokens": 99999 } },
+        })
+        .to_string()
+    };
+    std::fs::write(
+        transcripts.join("session.jsonl"),
+        [line("/work/app", 300), line("/work/lib", 100), "not json".to_string()].join("\n"),
+    )
+    .unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(40.0)))
+        .mount(&h.server)
+        .await;
+
+    collect_usage(&h.last, Some(h.history_path()), None).await;
+
+    let stored = history::load_history_at(&h.history_path());
+    let latest = stored.snapshots.last().unwrap();
+    assert_eq!(latest.projects.get("/work/app"), Some(&300));
+    assert_eq!(latest.projects.get("/work/lib"), Some(&100));
+
+    let shares = history::project_usage(&stored.snapshots, "Current session", 0);
+    assert_eq!(shares[0].project, "/work/app");
+    assert!((shares[0].usage_pct - 0.225).abs() < 1e-9);
+    assert!((shares[1].usage_pct - 0.075).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn unwritable_history_is_queued_until_the_disk_recovers() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("token-1", far_future_ms()).await;
+    // The parent directory doesn't exist yet, so the write fails
+    let missing_dir = h.dir.path().join("not-yet");
+    let history_path = missing_dir.join("usage_history.json");
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(15.0)))
+        .mount(&h.server)
+        .await;
+
+    let result = collect_usage(&h.last, Some(history_path.clone()), None).await;
+    assert_eq!(session_pct(&result), Some(0.15));
+    assert!(!history_path.exists());
+    assert!(history::pending_count() >= 1);
+
+    std::fs::create_dir(&missing_dir).unwrap();
+    assert!(history::retry_pending());
+    assert_eq!(history::pending_count(), 0);
+    assert_eq!(history::load_history_at(&history_path).snapshots.len(), 1);
+}
+
+#[tokio::test]
+async fn rate_limited_request_is_retried() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("token-1", far_future_ms()).await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&h.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(10.0)))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+
+    let result = collect_usage(&h.last, None, None).await;
+    assert_eq!(result.usage_error, None);
+    assert_eq!(session_pct(&result), Some(0.10));
+    assert!(!result.rate_limited);
+}
+
+#[tokio::test]
+async fn persistent_rate_limit_serves_last_good_data() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("token-1", far_future_ms()).await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(33.0)))
+        .up_to_n_times(1)
+        .mount(&h.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "0"))
+        .mount(&h.server)
+        .await;
+
+    let first = collect_usage(&h.last, None, None).await;
+    assert_eq!(session_pct(&first), Some(0.33));
+
+    let second = collect_usage(&h.last, None, None).await;
+    assert!(second.rate_limited);
+    assert_eq!(second.usage_error, None);
+    assert_eq!(session_pct(&second), Some(0.33));
+}
+
+#[tokio::test]
+async fn long_retry_after_pauses_requests_until_the_cooldown_ends() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("token-1", far_future_ms()).await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "600"))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+
+    let first = collect_usage(&h.last, None, None).await;
+    assert!(first.rate_limited);
+    let until = first.cooldown_until.expect("cooldown started");
+    assert!(until > chrono::Utc::now().timestamp() + 500);
+
+    // Served without another request while cooling down
+    let second = collect_usage(&h.last, None, None).await;
+    assert!(second.rate_limited);
+    assert_eq!(second.cooldown_until, Some(until));
+}
+
+#[tokio::test]
+async fn unauthorized_triggers_refresh_and_retry() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("stale-token", far_future_ms()).await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .and(header("authorization", "Bearer stale-token"))
+        .respond_with(ResponseTemplate::new(401))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/oauth/token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "fresh-token",
+            "refresh_token": "refresh-2",
+            "expires_in": 28800,
+        })))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .and(header("authorization", "Bearer fresh-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(5.0)))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+
+    let result = collect_usage(&h.last, None, None).await;
+    assert_eq!(result.usage_error, None);
+    assert_eq!(session_pct(&result), Some(0.05));
+
+    let oauth = h.stored_oauth();
+    assert_eq!(oauth["accessToken"], "fresh-token");
+    assert_eq!(oauth["refreshToken"], "refresh-2");
+    // Fields we don't manage survive the write-back
+    assert_eq!(oauth["subscriptionType"], "max");
+}
+
+#[tokio::test]
+async fn expired_token_is_refreshed_before_the_request() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("expired-token", chrono::Utc::now().timestamp_millis() - 1000).await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/oauth/token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "fresh-token",
+            "expires_in": 28800,
+        })))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .and(header("authorization", "Bearer fresh-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(61.0)))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+
+    let result = collect_usage(&h.last, None, None).await;
+    assert_eq!(session_pct(&result), Some(0.61));
+    // No new refresh token issued, so the old one is kept
+    assert_eq!(h.stored_oauth()["refreshToken"], "refresh-1");
+}
+
+#[tokio::test]
+async fn rejected_refresh_reports_unauthorized() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("revoked-token", far_future_ms()).await;
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&h.server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/oauth/token"))
+        .respond_with(ResponseTemplate::new(400))
+        .mount(&h.server)
+        .await;
+
+    let result = collect_usage(&h.last, None, None).await;
+    assert!(result.usage.is_none());
+    assert!(result.usage_error.unwrap().contains("Unauthorized"));
+}
+
+#[tokio::test]
+async fn failed_fetch_serves_stale_data_with_error() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("token-1", far_future_ms()).await;
+    let cache_path = h.dir.path().join("last_usage.json");
+
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(usage_body(42.0)))
+        .up_to_n_times(1)
+        .mount(&h.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/api/oauth/usage"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+        .mount(&h.server)
+        .await;
+
+    let first = collect_usage(&h.last, Some(h.history_path()), Some(cache_path.clone())).await;
+    assert!(!first.stale);
+    let cached = usage_api::load_cached(&cache_path).expect("cache written");
+    assert_eq!(Some(cached.fetched_at), first.last_updated);
+
+    let second = collect_usage(&h.last, Some(h.history_path()), Some(cache_path)).await;
+    assert!(second.stale);
+    assert!(!second.rate_limited);
+    assert!(second.usage_error.is_some());
+    assert_eq!(session_pct(&second), Some(0.42));
+    assert_eq!(second.last_updated, first.last_updated);
+    // Only the fresh response is recorded
+    assert_eq!(history::load_history_at(&h.history_path()).snapshots.len(), 1);
+
+    // Another profile doesn't fall back to this one's data
+    let work = UsageSource { profile: "work".to_string(), backend: Backend::ClaudeCode };
+    let other = collect_usage_from(&work, &h.last, None, Some(h.dir.path().join("last_usage.work.json"))).await;
+    assert!(other.usage.is_none() && !other.stale);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn costs_are_summed_from_ccusage_output() {
+    let _env = ENV_LOCK.lock().await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let today = chrono::Local::now().date_naive();
+    let day = |n: u64| {
+        today
+            .checked_sub_days(chrono::Days::new(n))
+            .unwrap()
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+    let output = json!({
+        "daily": [
+            { "date": day(20), "totalCost": 4.0 },
+            { "date": day(3), "totalCost": 2.5 },
+            { "date": day(0), "totalCost": 1.25 },
+        ]
+    });
+
+    let script = dir.path().join("ccusage");
+    std::fs::write(&script, format!("#!/bin/sh\ncat <<'JSON'\n{}\nJSON\n", output)).unwrap();
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    std::env::set_var(CCUSAGE_PATH_ENV_VAR, &script);
+
+    let costs = ccusage::fetch_costs(&CostCache::new()).await.unwrap();
+    assert_eq!(costs.today, 1.25);
+    assert_eq!(costs.week, 3.75);
+    assert_eq!(costs.month, 7.75);
+}
+
+#[tokio::test]
+async fn admin_api_usage_is_measured_against_budgets() {
+    let _env = ENV_LOCK.lock().await;
+    let h = Harness::start("unused", far_future_ms()).await;
+    std::env::set_var(ADMIN_KEY_ENV_VAR, "sk-ant-admin-test");
+
+    let today = chrono::Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+    Mock::given(method("GET"))
+        .and(path("/v1/organizations/usage_report/messages"))
+        .and(header("x-api-key", "sk-ant-admin-test"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "data": [{
+                "starting_at": today.to_rfc3339(),
+                "ending_at": (today + chrono::Duration::days(1)).to_rfc3339(),
+                "results": [{ "uncached_input_tokens": 30000, "output_tokens": 10000 }],
+            }],
+            "has_more": false,
+            "next_page": null,
+        })))
+        .expect(1)
+        .mount(&h.server)
+        .await;
+
+    let source = UsageSource {
+        profile: "work".to_string(),
+        backend: Backend::AdminApi {
+            budgets: AdminBudgets { daily_tokens: Some(100_000), ..Default::default() },
+        },
+    };
+    let result = collect_usage_from(&source, &h.last, None, None).await;
+    std::env::remove_var(ADMIN_KEY_ENV_VAR);
+
+    assert_eq!(result.usage_error, None);
+    let usage = result.usage.unwrap();
+    assert_eq!(usage.limits[0].label, usage_api::ADMIN_TODAY_LABEL);
+    assert_eq!(usage.limits[0].usage_pct, 0.4);
+}
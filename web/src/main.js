@@ -33,6 +33,8 @@ const SPARK_PAD_BOTTOM = 2;
 
 const sparklineData = {};
 const sparklineOffsets = {};
+// Chart points per limit label, from get_usage_series
+const usageSeries = {};
 
 function snapToHour(d) {
   const min = d.getMinutes();
@@ -91,7 +93,28 @@ function setTheme(theme) {
   if (toggle) toggle.checked = theme === "dark";
 }
 
-async function fetchAndRender(silent = false) {
+// Sources still outstanding in the current refresh; the spinner stops when empty
+const pendingSources = new Set();
+
+// Newest refresh generation seen per event, so a slow result from an older
+// refresh can't overwrite a newer one
+const latestGeneration = { usage: 0, costs: 0, history: 0 };
+
+function isSuperseded(name, payload) {
+  if (payload.generation == null) return false;
+  if (payload.generation < latestGeneration[name]) return true;
+  latestGeneration[name] = payload.generation;
+  return false;
+}
+
+function finishSource(name) {
+  pendingSources.delete(name);
+  if (pendingSources.size === 0) {
+    document.getElementById("refresh-btn").classList.remove("spinning");
+  }
+}
+
+function fetchAndRender(silent = false) {
   const btn = document.getElementById("refresh-btn");
   if (!silent) btn.classList.add("spinning");
 
@@ -104,49 +127,86 @@ async function fetchAndRender(silent = false) {
     renderCosts(lastCostsData);
   }
 
-  // Fire both requests concurrently; render each section as it arrives
-  const usagePromise = invoke("get_usage_data");
-  const costsPromise = invoke("get_costs_data");
+  // Results arrive as usage-ready / costs-ready / history-ready events
+  pendingSources.add("usage");
+  pendingSources.add("costs");
+  invoke("get_all_data").catch((e) => {
+    console.error("Failed to start refresh:", e);
+    pendingSources.clear();
+    btn.classList.remove("spinning");
+  });
+  resetCountdown();
+}
 
-  // Usage renders first (~1s) without waiting for costs
-  try {
-    const usageData = await usagePromise;
-    lastUsageData = usageData;
-    renderUsage(usageData);
-    if (usageData.rate_limited) {
-      refreshInterval = Math.min(refreshInterval * 2, 300);
-      console.log("Rate limited, backing off to " + refreshInterval + "s");
-      startAutoRefresh();
-    } else if (refreshInterval > 60) {
-      refreshInterval = 60;
-      startAutoRefresh();
-    }
-    if (usageData.usage && usageData.usage.plan) {
-      updatePlanFromAPI(usageData.usage.plan);
-    }
-    document.getElementById("timestamp").textContent = "Updated " + usageData.timestamp;
-  } catch (e) {
-    console.error("Failed to fetch usage:", e);
+function handleUsageReady(usageData) {
+  if (isSuperseded("usage", usageData)) return;
+  lastUsageData = usageData;
+  renderUsage(usageData);
+  // Includes the snapshot this refresh recorded
+  redrawWithSeries(usageData);
+  if (usageData.cooldown_until) {
+    // Polls are answered from the cache until the cooldown ends, so wait it out
+    const secs = usageData.cooldown_until - Math.floor(Date.now() / 1000) + 1;
+    refreshInterval = Math.max(secs, 60);
+    console.log("Rate limited, pausing refresh for " + refreshInterval + "s");
+    startAutoRefresh();
+  } else if (usageData.rate_limited) {
+    refreshInterval = Math.min(refreshInterval * 2, 300);
+    console.log("Rate limited, backing off to " + refreshInterval + "s");
+    startAutoRefresh();
+  } else if (refreshInterval > 60) {
+    refreshInterval = 60;
+    startAutoRefresh();
   }
+  if (usageData.usage && usageData.usage.plan) {
+    updatePlanFromAPI(usageData.usage.plan);
+  }
+  document.getElementById("timestamp").textContent = usageData.stale && usageData.last_updated
+    ? "Last updated " + formatAgo(usageData.last_updated)
+    : "Updated " + usageData.timestamp;
 
-  // Costs render when ready
-  try {
-    const costsData = await costsPromise;
-    lastCostsData = costsData;
-    renderCosts(costsData);
-  } catch (e) {
-    console.error("Failed to fetch costs:", e);
+  invoke("get_forecast").then(renderForecast).catch((e) => console.error("Failed to fetch forecast:", e));
+  invoke("get_health").then(renderHealth).catch((e) => console.error("Failed to fetch health:", e));
+  finishSource("usage");
+}
+
+function handleCostsReady(costsData) {
+  if (isSuperseded("costs", costsData)) return;
+  lastCostsData = costsData;
+  renderCosts(costsData);
+  // An early stale result is followed by the fresh one
+  if (!costsData.refreshing) finishSource("costs");
+}
+
+// History is ready to query before usage usually lands; redraw the cached
+// sparklines from it until fresh usage arrives
+function handleHistoryReady(payload) {
+  if (isSuperseded("history", payload)) return;
+  if (lastUsageData && pendingSources.has("usage")) {
+    redrawWithSeries(lastUsageData);
   }
+}
 
-  if (!silent) btn.classList.remove("spinning");
-  resetCountdown();
+// Peak usage per interval for each limit's chart over the last week: five
+// minutes wide for session windows, an hour for the weekly limits
+async function loadUsageSeries(limits) {
+  await Promise.all(limits.map(async (limit) => {
+    const resolution = isSessionLimit(limit.label) ? "five_minutes" : "hour";
+    try {
+      const points = await invoke("get_usage_series", { label: limit.label, resolution });
+      usageSeries[limit.label] = points.map((p) => ({ timestamp: p.timestamp, value: p.max }));
+    } catch (e) {
+      console.error("Failed to fetch usage series:", e);
+    }
+  }));
 }
 
-function getHistoryForLabel(history, label) {
-  if (!history || !Array.isArray(history)) return [];
-  return history
-    .filter((s) => s.buckets && s.buckets[label] !== undefined)
-    .map((s) => ({ timestamp: s.timestamp, value: s.buckets[label] }));
+function redrawWithSeries(data) {
+  if (!data || !data.usage || !data.usage.limits) return;
+  loadUsageSeries(data.usage.limits).then(() => {
+    // Unless newer usage was rendered meanwhile
+    if (lastUsageData === data) renderUsage(data);
+  });
 }
 
 const SHORT_DAY_NAMES = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
@@ -344,6 +404,9 @@ function renderUsage(data) {
 
   loading.style.display = "none";
 
+  // Stale data is still worth showing; the error is rendered above it
+  const showStale = data.stale && data.usage;
+
   if (data.usage_error) {
     errorEl.style.display = "block";
     errorEl.textContent = data.usage_error;
@@ -357,14 +420,29 @@ function renderUsage(data) {
       errorEl.appendChild(document.createElement("br"));
       errorEl.appendChild(loginBtn);
     }
-    document.getElementById("session-limits").innerHTML = "";
-    document.getElementById("weekly-section").style.display = "none";
-    document.getElementById("extra-section").style.display = "none";
-    return;
+    if (showStale && data.last_updated) {
+      const note = document.createElement("div");
+      note.className = "stale-note";
+      note.textContent = "Showing data from " + formatAgo(data.last_updated);
+      errorEl.appendChild(note);
+    }
+    if (!showStale) {
+      document.getElementById("session-limits").innerHTML = "";
+      document.getElementById("weekly-section").style.display = "none";
+      document.getElementById("extra-section").style.display = "none";
+      return;
+    }
+  } else if (data.display && data.display.retry_at) {
+    errorEl.style.display = "block";
+    errorEl.textContent = "";
+    const note = document.createElement("div");
+    note.className = "stale-note";
+    note.textContent = "Rate limited, cooling down until " + data.display.retry_at;
+    errorEl.appendChild(note);
+  } else {
+    errorEl.style.display = "none";
   }
 
-  errorEl.style.display = "none";
-
   const sessionEl = document.getElementById("session-limits");
   const weeklySection = document.getElementById("weekly-section");
   const weeklyEl = document.getElementById("weekly-limits");
@@ -375,15 +453,16 @@ function renderUsage(data) {
     return;
   }
 
-  const history = data.usage_history;
   const sessionLimits = data.usage.limits.filter((l) => isSessionLimit(l.label));
   const weeklyLimits = data.usage.limits.filter((l) => !isSessionLimit(l.label));
 
+  const displayPct = (data.display && data.display.limits) || {};
+
   function renderLimitItem(limit) {
     const pct = Math.min(100, Math.floor(limit.usage_pct * 100));
     const colorClass = getColorClass(pct);
     const resetText = limit.reset_at ? formatReset(limit.reset_at) : "";
-    const historyPoints = getHistoryForLabel(history, limit.label);
+    const historyPoints = usageSeries[limit.label] || [];
     const color = getColorForPct(pct);
 
     let sparkline;
@@ -400,7 +479,7 @@ function renderUsage(data) {
       <div class="limit-item">
         <div class="limit-header">
           <span class="limit-label">${escapeHtml(limit.label)}</span>
-          <span class="limit-pct" style="color: var(--${colorClass})">${pct}%</span>
+          <span class="limit-pct" style="color: var(--${colorClass})">${escapeHtml(displayPct[limit.label] || pct + "%")}</span>
         </div>
         <div class="progress-track">
           <div class="progress-fill ${colorClass}" style="width: ${pct}%"></div>
@@ -427,12 +506,13 @@ function renderUsage(data) {
     const eu = data.usage.extra_usage;
     const pct = Math.min(100, Math.floor(eu.utilization * 100));
     const colorClass = getColorClass(pct);
+    const spend = (data.display && data.display.extra_usage_spend) || formatCost(eu.used_credits);
     extraSection.style.display = "block";
     extraEl.innerHTML = `
       <div class="limit-item">
         <div class="limit-header">
           <span class="limit-label">Spend</span>
-          <span class="limit-pct" style="color: var(--${colorClass})">${pct}% (&pound;${eu.used_credits.toFixed(2)})</span>
+          <span class="limit-pct" style="color: var(--${colorClass})">${pct}% (${escapeHtml(spend)})</span>
         </div>
         <div class="progress-track">
           <div class="progress-fill ${colorClass}" style="width: ${pct}%"></div>
@@ -444,6 +524,27 @@ function renderUsage(data) {
   }
 }
 
+function renderForecast(forecast) {
+  const summaries = forecast.limits.map((l) => l.summary).filter(Boolean);
+  document.getElementById("weekly-forecast").textContent = summaries.join(" \u00b7 ");
+}
+
+function renderHealth(health) {
+  const el = document.getElementById("health-warning");
+  if (health.ok) {
+    el.style.display = "none";
+    return;
+  }
+  const failure = health.write_failures[0];
+  let text = "Can't save data to disk";
+  if (failure && failure.kind === "disk_full") text = "Disk is full - can't save data";
+  else if (failure && failure.kind === "read_only") text = "Disk is read-only - can't save data";
+  else if (failure && failure.kind === "permission_denied") text = "No permission to save data";
+  if (health.pending_snapshots > 0) text += ` (${health.pending_snapshots} snapshot(s) held in memory, retrying)`;
+  el.textContent = text;
+  el.style.display = "block";
+}
+
 function renderCosts(data) {
   const loading = document.getElementById("costs-loading");
   const errorEl = document.getElementById("costs-error");
@@ -454,21 +555,28 @@ function renderCosts(data) {
   if (data.costs_error) {
     errorEl.style.display = "block";
     errorEl.textContent = data.costs_error;
-    dataEl.style.display = "none";
-    return;
+    if (!data.costs) {
+      dataEl.style.display = "none";
+      return;
+    }
+  } else if (data.stale && data.last_updated) {
+    errorEl.style.display = "block";
+    errorEl.textContent = "Showing costs from " + formatAgo(data.last_updated) + (data.refreshing ? ", refreshing..." : "");
+  } else {
+    errorEl.style.display = "none";
   }
 
-  errorEl.style.display = "none";
-
   if (!data.costs) {
     dataEl.style.display = "none";
     return;
   }
 
   dataEl.style.display = "block";
-  document.getElementById("cost-today").textContent = formatCost(data.costs.today);
-  document.getElementById("cost-week").textContent = formatCost(data.costs.week);
-  document.getElementById("cost-month").textContent = formatCost(data.costs.month);
+  // Prefer the backend's strings so panel, tray and notifications agree
+  const formatted = data.formatted || {};
+  document.getElementById("cost-today").textContent = formatted.today || formatCost(data.costs.today);
+  document.getElementById("cost-week").textContent = formatted.week || formatCost(data.costs.week);
+  document.getElementById("cost-month").textContent = formatted.month || formatCost(data.costs.month);
 }
 
 function formatCost(value) {
@@ -476,6 +584,14 @@ function formatCost(value) {
   return "\u00a3" + value.toFixed(2);
 }
 
+// "just now" / "5 min ago" / "2h ago" for a Unix-seconds timestamp
+function formatAgo(unixSecs) {
+  const mins = Math.floor((Date.now() / 1000 - unixSecs) / 60);
+  if (mins < 1) return "just now";
+  if (mins < 60) return `${mins} min ago`;
+  return `${Math.floor(mins / 60)}h ago`;
+}
+
 function formatReset(isoString) {
   try {
     const reset = new Date(isoString);
@@ -558,12 +674,16 @@ async function loadPrefs() {
     console.error("Failed to load autostart pref:", e);
   }
 
-  const stayOnTop = localStorage.getItem(STAY_ON_TOP_KEY) === "true";
-  document.getElementById("stay-on-top-toggle").checked = stayOnTop;
-
-  // Notify tokens defaults to true (enabled) if never set
-  const notifyTokens = localStorage.getItem(NOTIFY_TOKENS_KEY) !== "false";
-  document.getElementById("notify-tokens-toggle").checked = notifyTokens;
+  try {
+    const settings = await invoke("get_settings");
+    document.getElementById("stay-on-top-toggle").checked = settings.panel.stay_on_top;
+    document.getElementById("notify-tokens-toggle").checked = settings.notifications.enabled;
+    setSnoozeUI(settings.notifications.snoozed_until);
+    document.getElementById("auto-check-toggle").checked = settings.updates.auto_check;
+    document.getElementById("auto-install-toggle").checked = settings.updates.auto_install;
+  } catch (e) {
+    console.error("Failed to load notification prefs:", e);
+  }
 
   document.getElementById("plan-select").value = localStorage.getItem(PLAN_KEY) || "";
   document.getElementById("fontsize-select").value = localStorage.getItem(FONTSIZE_KEY) || "13";
@@ -581,21 +701,53 @@ async function handleAutostartChange(e) {
 
 async function handleStayOnTopChange(e) {
   const enabled = e.target.checked;
-  localStorage.setItem(STAY_ON_TOP_KEY, enabled ? "true" : "false");
   try {
     await invoke("set_stay_on_top_pref", { enabled });
   } catch (err) {
     console.error("Failed to set stay-on-top pref:", err);
+    e.target.checked = !enabled;
   }
 }
 
 async function handleNotifyTokensChange(e) {
   const enabled = e.target.checked;
-  localStorage.setItem(NOTIFY_TOKENS_KEY, enabled ? "true" : "false");
   try {
     await invoke("set_notify_tokens_pref", { enabled });
   } catch (err) {
     console.error("Failed to set notify-tokens pref:", err);
+    e.target.checked = !enabled;
+  }
+}
+
+function setSnoozeUI(until) {
+  const select = document.getElementById("snooze-select");
+  const label = document.getElementById("snooze-label");
+  if (until && until * 1000 > Date.now()) {
+    const time = new Date(until * 1000).toLocaleTimeString([], { hour: "numeric", minute: "2-digit" });
+    label.textContent = `Snoozed until ${time}`;
+    if (!select.value) select.value = "active";
+  } else {
+    label.textContent = "Snooze notifications";
+    select.value = "";
+  }
+}
+
+async function handleUpdatePrefsChange() {
+  const autoCheck = document.getElementById("auto-check-toggle").checked;
+  const autoInstall = document.getElementById("auto-install-toggle").checked;
+  try {
+    await invoke("set_update_prefs", { autoCheck, autoInstall });
+  } catch (err) {
+    console.error("Failed to set update prefs:", err);
+  }
+}
+
+async function handleSnoozeChange(e) {
+  const duration = e.target.value || null;
+  try {
+    setSnoozeUI(await invoke("snooze_notifications", { duration }));
+  } catch (err) {
+    console.error("Failed to snooze notifications:", err);
   }
 }
 
@@ -661,6 +813,23 @@ async function checkForUpdates() {
   }
 }
 
+async function saveDiagnostics() {
+  const statusEl = document.getElementById("diagnostics-status");
+  statusEl.textContent = "Saving...";
+  try {
+    const path = await invoke("generate_diagnostics");
+    if (path === null) {
+      statusEl.textContent = "";
+      return;
+    }
+    statusEl.textContent = "Saved to " + path;
+    statusEl.title = path;
+  } catch (e) {
+    console.error("Diagnostics failed:", e);
+    statusEl.textContent = "Could not save: " + e;
+  }
+}
+
 async function installUpdate() {
   const statusEl = document.getElementById("update-status");
   statusEl.textContent = "Downloading update...";
@@ -765,13 +934,17 @@ document.addEventListener("DOMContentLoaded", () => {
   tooltip.className = "sparkline-tooltip";
   document.getElementById("app").appendChild(tooltip);
 
-  // Sync stay-on-top pref to Rust on startup
-  const stayOnTop = localStorage.getItem(STAY_ON_TOP_KEY) === "true";
-  invoke("set_stay_on_top_pref", { enabled: stayOnTop }).catch(() => {});
+  // The stay-on-top pref is a setting now; carry over one turned on here
+  if (localStorage.getItem(STAY_ON_TOP_KEY) === "true") {
+    invoke("set_stay_on_top_pref", { enabled: true }).catch(() => {});
+  }
+  localStorage.removeItem(STAY_ON_TOP_KEY);
 
-  // Sync notify-tokens pref to Rust on startup (defaults to true)
-  const notifyTokens = localStorage.getItem(NOTIFY_TOKENS_KEY) !== "false";
-  invoke("set_notify_tokens_pref", { enabled: notifyTokens }).catch(() => {});
+  // The notify-tokens pref is a setting now; carry over one turned off here
+  if (localStorage.getItem(NOTIFY_TOKENS_KEY) === "false") {
+    invoke("set_notify_tokens_pref", { enabled: false }).catch(() => {});
+  }
+  localStorage.removeItem(NOTIFY_TOKENS_KEY);
 
   document.getElementById("darkmode-toggle").addEventListener("change", (e) => {
     const theme = e.target.checked ? "dark" : "light";
@@ -790,10 +963,17 @@ document.addEventListener("DOMContentLoaded", () => {
   document.getElementById("autostart-toggle").addEventListener("change", handleAutostartChange);
   document.getElementById("stay-on-top-toggle").addEventListener("change", handleStayOnTopChange);
   document.getElementById("notify-tokens-toggle").addEventListener("change", handleNotifyTokensChange);
+  document.getElementById("snooze-select").addEventListener("change", handleSnoozeChange);
+  document.getElementById("auto-check-toggle").addEventListener("change", handleUpdatePrefsChange);
+  document.getElementById("auto-install-toggle").addEventListener("change", handleUpdatePrefsChange);
   document.getElementById("check-updates-link").addEventListener("click", (e) => {
     e.preventDefault();
     checkForUpdates();
   });
+  document.getElementById("diagnostics-link").addEventListener("click", (e) => {
+    e.preventDefault();
+    saveDiagnostics();
+  });
   // Sparkline navigation: delegated click handlers
   document.getElementById("usage-section").addEventListener("click", (e) => {
     const btn = e.target.closest(".sparkline-prev, .sparkline-next");
@@ -906,6 +1086,15 @@ document.addEventListener("DOMContentLoaded", () => {
     }
   });
 
+  listen("usage-ready", (e) => handleUsageReady(e.payload));
+  listen("costs-ready", (e) => handleCostsReady(e.payload));
+  listen("history-ready", (e) => handleHistoryReady(e.payload));
+
+  // The panel may have been restored detached before these listeners existed
+  invoke("get_settings")
+    .then((s) => setDetachedUI(s.panel.detached))
+    .catch((e) => console.error("Failed to load panel state:", e));
+
   listen("panel-detached", () => {
     setDetachedUI(true);
   });
@@ -914,6 +1103,8 @@ document.addEventListener("DOMContentLoaded", () => {
     setDetachedUI(false);
   });
 
+  listen("snooze-changed", (e) => setSnoozeUI(e.payload));
+
   listen("panel-shown", () => {
     fetchAndRender(true);
     startAutoRefresh();